@@ -0,0 +1,144 @@
+// Command line tool for manipulating levels without the editor, e.g. in build scripts.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use supaleve_rs::level::Level;
+use supaleve_rs::solver::{self, Limits};
+use supaleve_rs::{level_file, render};
+
+const USAGE: &str = "\
+Usage:
+  supaleve convert <input> <output>
+  supaleve extract <pack> <level number> <output>
+  supaleve insert <pack> <level number> <level file> [<output>]
+  supaleve render <input> <output.png> [--level <level number>] [--img <tile image dir>]
+  supaleve solve <input> [--level <level number>] [--time <seconds>] [--memory <MiB>]
+
+The format of the files is chosen by the extension: .SP, .MPX, .TXT, anything else is
+LEVELS.DAT. Level numbers start from 1. Render uses the built in tile images unless
+a directory of them is given. Solve tries every level unless one is given,
+with at most 10 seconds and 256 MiB per level by default.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("supaleve: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["convert", input, output] => convert(Path::new(input), Path::new(output)),
+        ["extract", pack, number, output] => extract(Path::new(pack), level_number(number)?, Path::new(output)),
+        ["insert", pack, number, level] => insert(Path::new(pack), level_number(number)?, Path::new(level), Path::new(pack)),
+        ["insert", pack, number, level, output] => insert(Path::new(pack), level_number(number)?, Path::new(level), Path::new(output)),
+        ["render", input, output, options @ ..] => render(Path::new(input), Path::new(output), options),
+        ["solve", input, options @ ..] => solve(Path::new(input), options),
+        _ => Err(USAGE.to_owned()),
+    }
+}
+
+/// Parses a level number, returning the index of the level.
+fn level_number(number: &str) -> Result<usize, String> {
+    match number.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(format!("invalid level number {:?}", number)),
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<Level>, String> {
+    level_file::read(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn write(path: &Path, levels: &[Level]) -> Result<(), String> {
+    level_file::write(path, levels).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn check_level_index(levels: usize, index: usize, path: &Path) -> Result<(), String> {
+    match index < levels {
+        true => Ok(()),
+        false => Err(format!("{}: there is no level {}, only {}", path.display(), index + 1, levels)),
+    }
+}
+
+fn convert(input: &Path, output: &Path) -> Result<(), String> {
+    write(output, &read(input)?)
+}
+
+fn extract(pack: &Path, index: usize, output: &Path) -> Result<(), String> {
+    let mut levels = read(pack)?;
+    check_level_index(levels.len(), index, pack)?;
+    write(output, &[levels.swap_remove(index)])
+}
+
+fn insert(pack: &Path, index: usize, level: &Path, output: &Path) -> Result<(), String> {
+    let mut levels = read(pack)?;
+    check_level_index(levels.len(), index, pack)?;
+    let mut new_levels = read(level)?;
+    if new_levels.len() != 1 {
+        return Err(format!("{}: expected one level, found {}", level.display(), new_levels.len()));
+    }
+    levels[index] = new_levels.remove(0);
+    write(output, &levels)
+}
+
+fn render(input: &Path, output: &Path, options: &[&str]) -> Result<(), String> {
+    let mut index = 0;
+    let mut img_dir = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match (*option, options.next()) {
+            ("--level", Some(number)) => index = level_number(number)?,
+            ("--img", Some(dir)) => img_dir = Some(PathBuf::from(dir)),
+            _ => return Err(USAGE.to_owned()),
+        }
+    }
+
+    let levels = read(input)?;
+    check_level_index(levels.len(), index, input)?;
+    let tile_images = match img_dir {
+        Some(dir) => render::load_tile_images(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?,
+        None => render::embedded_tile_images().map_err(|e| format!("built in tile images: {}", e))?,
+    };
+    render::render_level(&levels[index], &tile_images)
+        .save(output)
+        .map_err(|e| format!("{}: {}", output.display(), e))
+}
+
+fn solve(input: &Path, options: &[&str]) -> Result<(), String> {
+    let mut index = None;
+    let mut limits = Limits::default();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match (*option, options.next()) {
+            ("--level", Some(number)) => index = Some(level_number(number)?),
+            ("--time", Some(seconds)) => limits.time = seconds.parse().ok()
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                .ok_or_else(|| format!("invalid time {:?}", seconds))?,
+            ("--memory", Some(mib)) => limits.memory = mib.parse::<usize>()
+                .map_err(|_| format!("invalid memory limit {:?}", mib))? << 20,
+            _ => return Err(USAGE.to_owned()),
+        }
+    }
+
+    let levels = read(input)?;
+    let indices = match index {
+        Some(index) => {
+            check_level_index(levels.len(), index, input)?;
+            index..index + 1
+        }
+        None => 0..levels.len(),
+    };
+    for index in indices {
+        let level = &levels[index];
+        println!("{:03} {}: {}", index + 1, level.info.title().trim(), solver::solve(level, limits));
+    }
+    Ok(())
+}
@@ -0,0 +1,164 @@
+// Rectangular parts of levels, for copying, moving, mirroring and turning structures
+// within a level and between levels.
+
+use crate::level::{Level, SpecialPort};
+use crate::tile::{Tile, Transform};
+
+/// A rectangle of tiles in a level.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TileRect {
+    pub col: usize,
+    pub row: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl TileRect {
+    /// The rectangle with the tiles at `a` and `b` in opposite corners.
+    pub fn from_corners(level: &Level, a: usize, b: usize) -> Self {
+        let (a_col, a_row) = level.col_row(a);
+        let (b_col, b_row) = level.col_row(b);
+        Self {
+            col: a_col.min(b_col),
+            row: a_row.min(b_row),
+            width: a_col.abs_diff(b_col) + 1,
+            height: a_row.abs_diff(b_row) + 1,
+        }
+    }
+
+    /// The rectangle of the whole level.
+    pub fn of_level(level: &Level) -> Self {
+        Self { col: 0, row: 0, width: level.width(), height: level.height() }
+    }
+
+    pub fn contains(&self, col: usize, row: usize) -> bool {
+        (self.col..self.col + self.width).contains(&col) && (self.row..self.row + self.height).contains(&row)
+    }
+
+    /// The indices of the tiles in the rectangle, row by row.
+    pub fn indices(&self, level: &Level) -> Vec<usize> {
+        (self.row..self.row + self.height)
+            .flat_map(|row| (self.col..self.col + self.width).map(move |col| (col, row)))
+            .map(|(col, row)| level.index(col, row))
+            .collect()
+    }
+}
+
+/// Tiles copied from a level, along with the special port table entries of the gravity
+/// ports among them.
+#[derive(Clone, Debug)]
+pub struct Block {
+    width: usize,
+    height: usize,
+    /// The tiles, row by row.
+    tiles: Vec<Tile>,
+    /// The special ports, with the positions as indices of `tiles`.
+    ports: Vec<SpecialPort>,
+}
+
+impl Block {
+    pub fn copy(level: &Level, rect: TileRect) -> Self {
+        let indices = rect.indices(level);
+        let ports = level.info.special_ports().into_iter()
+            .filter_map(|port| Some(SpecialPort {
+                position: indices.iter().position(|&i| i == port.position)?,
+                ..port
+            }))
+            .collect();
+        Self {
+            width: rect.width,
+            height: rect.height,
+            tiles: indices.iter().map(|&i| level.play_area[i]).collect(),
+            ports,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The block mirrored or turned, with the directional tiles remapped accordingly.
+    /// Turning swaps the width and the height.
+    pub fn transformed(&self, transform: Transform) -> Self {
+        let (width, height) = match transform {
+            Transform::RotateClockwise => (self.height, self.width),
+            _ => (self.width, self.height),
+        };
+        // The index in the transformed block of the tile at `i` in this one.
+        let new_index = |i: usize| {
+            let (col, row) = (i % self.width, i / self.width);
+            let (col, row) = match transform {
+                Transform::FlipHorizontal => (self.width - 1 - col, row),
+                Transform::FlipVertical => (col, self.height - 1 - row),
+                Transform::RotateClockwise => (self.height - 1 - row, col),
+            };
+            col + row * width
+        };
+        let mut tiles = vec![Tile::Empty; self.tiles.len()];
+        for (i, &tile) in self.tiles.iter().enumerate() {
+            tiles[new_index(i)] = tile.transformed(transform);
+        }
+        let ports = self.ports.iter()
+            .map(|port| SpecialPort { position: new_index(port.position), ..*port })
+            .collect();
+        Self { width, height, tiles, ports }
+    }
+
+    /// The positions in the level of the tiles of the block when its top left corner is
+    /// at `col`, `row`, and the tiles. Tiles that would end up outside the level are left out.
+    pub fn tiles_at(&self, level: &Level, col: isize, row: isize) -> Vec<(usize, Tile)> {
+        self.tiles.iter().enumerate()
+            .filter_map(|(i, &tile)| {
+                let index = self.level_index(level, col, row, i)?;
+                Some((index, tile))
+            })
+            .collect()
+    }
+
+    fn level_index(&self, level: &Level, col: isize, row: isize, i: usize) -> Option<usize> {
+        let col = col.checked_add((i % self.width) as isize).filter(|&c| c >= 0 && (c as usize) < level.width())?;
+        let row = row.checked_add((i / self.width) as isize).filter(|&r| r >= 0 && (r as usize) < level.height())?;
+        Some(level.index(col as usize, row as usize))
+    }
+
+    /// Places the block in the level with its top left corner at `col`, `row`. Gravity
+    /// ports keep their special port settings as long as the table has room for them.
+    /// Returns the part of the level covered by the block, if any.
+    pub fn paste(&self, level: &mut Level, col: isize, row: isize) -> Option<TileRect> {
+        let tiles = self.tiles_at(level, col, row);
+        for &(index, tile) in &tiles {
+            level.set_tile(index, tile);
+        }
+        level.remove_stale_special_ports();
+        for port in &self.ports {
+            if let Some(position) = self.level_index(level, col, row, port.position) {
+                level.set_special_port(SpecialPort { position, ..*port }).ok();
+            }
+        }
+        let (first, last) = (tiles.first()?.0, tiles.last()?.0);
+        Some(TileRect::from_corners(level, first, last))
+    }
+}
+
+/// The whole level mirrored or turned. Turning a level swaps its width and height.
+pub fn transform_level(level: &Level, transform: Transform) -> Level {
+    let block = Block::copy(level, TileRect::of_level(level)).transformed(transform);
+    let mut transformed = Level::new(block.width, block.height);
+    transformed.info = level.info.clone();
+    transformed.info.set_special_ports(&[]).unwrap();
+    transformed.demo = level.demo.clone();
+    block.paste(&mut transformed, 0, 0);
+    transformed
+}
+
+/// Empties the tiles of the rectangle.
+pub fn clear(level: &mut Level, rect: TileRect) {
+    for index in rect.indices(level) {
+        level.set_tile(index, Tile::Empty);
+    }
+    level.remove_stale_special_ports();
+}
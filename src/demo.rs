@@ -0,0 +1,103 @@
+// Demos: recordings of the player's input, stored after the level in .SP files. The first
+// byte is the number of the level the demo was recorded on. Each of the following bytes
+// holds an input in its low four bits and the number of frames it lasts, minus one, in
+// its high four bits. The demo ends with 0xff.
+
+use crate::level::{Direction, LevelError};
+use crate::simulation::Action;
+
+/// Number of frames the game takes to move Murphy one tile, which is one step of the
+/// simulation.
+pub const FRAMES_PER_STEP: usize = 8;
+
+/// Most frames a single byte of a demo can last.
+const MAX_FRAMES: usize = 16;
+
+const END: u8 = 0xff;
+
+/// The inputs of the game, in the order of their values in demos.
+const INPUTS: [Action; 10] = [
+    Action::None,
+    Action::Move(Direction::Up),
+    Action::Move(Direction::Left),
+    Action::Move(Direction::Down),
+    Action::Move(Direction::Right),
+    Action::Snap(Direction::Up),
+    Action::Snap(Direction::Left),
+    Action::Snap(Direction::Down),
+    Action::Snap(Direction::Right),
+    Action::DropDisk,
+];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Demo {
+    /// The number of the level the demo was recorded on, starting from 1.
+    pub level_number: u8,
+    /// What Murphy does in each step.
+    pub actions: Vec<Action>,
+}
+
+impl Demo {
+    /// Reads the demo from the bytes following a level. Each step takes the input of its
+    /// first frame.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LevelError> {
+        let (&level_number, inputs) = bytes.split_first().ok_or(LevelError::InvalidDemo(0))?;
+        let mut frames = vec![];
+        for (offset, &byte) in inputs.iter().enumerate() {
+            if byte == END {
+                break;
+            }
+            let action = *INPUTS.get((byte & 0x0f) as usize).ok_or(LevelError::InvalidDemo(offset + 1))?;
+            frames.extend(std::iter::repeat_n(action, (byte >> 4) as usize + 1));
+        }
+        let actions = frames.into_iter().step_by(FRAMES_PER_STEP).collect();
+        Ok(Self { level_number, actions })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.level_number];
+        for run in self.actions.chunk_by(|a, b| a == b) {
+            let input = INPUTS.iter().position(|&action| action == run[0]).unwrap() as u8;
+            let mut frames = run.len() * FRAMES_PER_STEP;
+            while frames > 0 {
+                let count = frames.min(MAX_FRAMES);
+                bytes.push(((count - 1) as u8) << 4 | input);
+                frames -= count;
+            }
+        }
+        bytes.push(END);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut actions = INPUTS.to_vec();
+        actions.extend([Action::Move(Direction::Right); 5]); // longer than one byte can hold
+        actions.push(Action::None);
+        let demo = Demo { level_number: 7, actions };
+        let bytes = demo.to_bytes();
+        assert_eq!(bytes.first(), Some(&7));
+        assert_eq!(bytes.last(), Some(&END));
+        let read = Demo::from_bytes(&bytes).unwrap();
+        assert_eq!(read, demo);
+        assert_eq!(read.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn runs_are_split_into_bytes() {
+        let demo = Demo { level_number: 1, actions: vec![Action::Move(Direction::Down); 3] };
+        // 24 frames of input 3: 16 frames and 8 frames.
+        assert_eq!(demo.to_bytes(), [1, 0xf3, 0x73, END]);
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert!(matches!(Demo::from_bytes(&[1, 0x00, 0x0a, END]), Err(LevelError::InvalidDemo(2))));
+        assert!(matches!(Demo::from_bytes(&[]), Err(LevelError::InvalidDemo(0))));
+    }
+}
@@ -1,21 +1,40 @@
 
 use std::marker::Copy;
+use std::rc::Rc;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-use egui::{Layout, Align, vec2, ImageButton, Sense, Color32, InputState, Key, hex_color, containers::ComboBox};
+use egui::{Align, vec2, Vec2, Pos2, Sense, Color32, InputState, Key, PointerButton, hex_color, containers::ComboBox, ScrollArea, Rect, Mesh, Shape, Button, Stroke};
 
-use crate::{tool_panel::{Tile, ToolPanel, OperatingMode}, images::Images};
+use crate::{tool_panel::{Brush, ToolPanel, OperatingMode}, images::Images, tile::{Tile, Transform}};
+use crate::level::{Direction, Level, SpecialPort};
+use crate::block::{self, Block, TileRect};
+use crate::history::History;
+use crate::levels_dat::LEVEL_COUNT;
+use crate::reachability::unreachable_targets;
+use crate::status_panel::WARNING_COLOR;
+use crate::validate::instabilities;
+use crate::simulation::{Action, Simulation};
+use crate::demo::Demo;
+use crate::solver::{self, Limits, Solution};
 
-const PLAY_AREA_WIDTH: usize = 60;
-const PLAY_AREA_HEIGHT: usize = 24;
-const PLAY_AREA_SIZE: usize = PLAY_AREA_WIDTH * PLAY_AREA_HEIGHT;
+/// Size of the tiles on the screen at 100% zoom.
+const TILE_SIZE: f32 = 32.;
 
-fn col_row(index: usize) -> (usize, usize) {
-    (index % PLAY_AREA_WIDTH, index / PLAY_AREA_WIDTH)
-}
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.;
 
-fn index(col: usize, row: usize) -> usize {
-    return col + PLAY_AREA_WIDTH * row
-}
+const UNREACHABLE_TINT: Color32 = Color32::from_rgb(0xff, 0x60, 0x60);
+
+/// Colours drawn over the exploding tiles and the sparking bugs of a simulation.
+const EXPLOSION_COLOR: Color32 = Color32::from_rgba_premultiplied(0xc0, 0x60, 0x00, 0xc0);
+const SPARK_COLOR: Color32 = Color32::from_rgba_premultiplied(0x60, 0x60, 0x30, 0x60);
+
+/// Colour of the way Murphy takes in a solution.
+const SOLUTION_COLOR: Color32 = Color32::from_rgb(0x40, 0xc0, 0xff);
+
+/// Time between the steps of a running simulation.
+const SIMULATION_STEP_SECONDS: f64 = 0.2;
 
 fn minmax<T : Ord + Copy>(a: T, b: T) -> (T, T) {
     (a.min(b), a.max(b))
@@ -40,176 +59,1003 @@ impl LineMode {
 #[derive(Copy, Clone, Debug)]
 enum ToolMode {
     Nop,
-    Draw { tile: Tile },
-    Line { tile: Tile, start: usize, mode: LineMode },
-    Rect { tile: Tile, start: usize },
+    Draw { brush: Brush },
+    Line { brush: Brush, start: usize, mode: LineMode },
+    Rect { brush: Brush, start: usize },
+    /// Bucket fill of the tiles connected to the one under the pointer.
+    Fill { brush: Brush },
 }
 
 impl ToolMode {
+    fn brush(&self) -> Option<Brush> {
+        match *self {
+            Self::Nop => None,
+            Self::Draw { brush } | Self::Line { brush, .. } | Self::Rect { brush, .. } | Self::Fill { brush }
+                => Some(brush),
+        }
+    }
+
     fn toggle_line_mode(&self) -> Self {
         match *self {
-            Self::Line { tile, start, mode } => Self::Line { tile, start, mode: mode.toggle() },
+            Self::Line { brush, start, mode } => Self::Line { brush, start, mode: mode.toggle() },
             other => other
         }
     }
 }
 
+/// What the pointer is doing in select mode.
+#[derive(Copy, Clone, Debug)]
+enum SelectAction {
+    /// Dragging out the selection from the tile at `start`.
+    Select { start: usize },
+    /// Dragging the selected tiles from the tile at `from` to the one at `to`.
+    Move { from: usize, to: usize },
+    /// Placing the clipboard with its top left corner at the tile at `at`.
+    Paste { at: Option<usize> },
+}
+
+/// Where the actions of Murphy come from in a simulation.
+#[derive(Clone, Debug, PartialEq)]
+enum MurphyControl {
+    /// Murphy stays put, for watching what the level does by itself.
+    None,
+    /// The player moves Murphy with the keyboard. The actions are recorded for a demo.
+    Player { recorded: Vec<Action> },
+    /// Murphy does what the demo of the level says.
+    Demo(Vec<Action>),
+}
+
+/// The outcome of searching for a solution of a level, with the level as it was.
+struct SolvedLevel {
+    index: usize,
+    level: Level,
+    solution: Solution,
+    path: Vec<usize>, // the positions of Murphy along the way
+}
+
 pub struct EditorPanel
 {
     heading: String,
-    play_area: [Tile; PLAY_AREA_SIZE],
-    highlight: [bool; PLAY_AREA_SIZE],
+    levels: Vec<Level>,
+    histories: Vec<History>, // one for each level
+    stroke_start: Option<Level>, // the level before the freehand stroke being drawn
+    stroke_covered: Vec<bool>, // the tiles already drawn by the freehand stroke
+    highlight: Vec<bool>,
     tool_mode: Option<ToolMode>,
-    images: Images, // TODO: share this between panels
+    images: Rc<Images>,
     ptr_primary: bool, // XXX: workaround for not detecting button release events 
     ptr_secondary: bool,
     selected_level_index: usize,
-    selected_tile_index: Option<usize>,
+    selection: Option<TileRect>,
+    select_action: Option<SelectAction>,
+    clipboard: Option<Block>, // kept when switching levels, for reusing structures
+    hovered_tile_index: Option<usize>,
+    scroll_to_selected: bool,
+    show_unreachable: bool,
+    unreachable: Vec<bool>, // the infotrons, terminals and exits Murphy can't get to
+    show_unstable: bool,
+    zoom: f32, // kept when switching levels
+    fit_pending: bool, // fit the level to the window when it's next drawn
+    scroll_offset: Option<Vec2>, // where to scroll the level to when it's next drawn
+    panning: bool, // dragging the level with the middle button, or with Space held down
+    simulation: Option<Simulation>, // shown instead of the level while it's there
+    simulation_running: bool,
+    next_step_time: f64,
+    murphy_control: MurphyControl,
+    pending_action: Action, // the last action the player chose since the previous step
+    solving: Option<(usize, Level, JoinHandle<Solution>)>, // the level being solved in the background
+    solved: Option<SolvedLevel>,
 }
 
 impl EditorPanel {
-    pub fn new(heading: &str) -> Self {
+    pub(crate) fn new(heading: &str, images: Rc<Images>) -> Self {
         Self {
             heading: String::from(heading),
-            play_area: [Tile::Empty; PLAY_AREA_SIZE],
-            highlight: [false; PLAY_AREA_SIZE],
+            levels: vec![Level::default(); LEVEL_COUNT],
+            histories: (0..LEVEL_COUNT).map(|_| History::default()).collect(),
+            stroke_start: None,
+            stroke_covered: vec![],
+            highlight: vec![false; Level::default().play_area.len()],
             tool_mode: None,
-            images: Images::new(),
+            images,
             ptr_primary: false,
             ptr_secondary: false,
             selected_level_index: 0,
-            selected_tile_index: None
+            selection: None,
+            select_action: None,
+            clipboard: None,
+            hovered_tile_index: None,
+            scroll_to_selected: false,
+            show_unreachable: false,
+            unreachable: vec![],
+            show_unstable: true,
+            zoom: 1.,
+            fit_pending: false,
+            scroll_offset: None,
+            panning: false,
+            simulation: None,
+            simulation_running: false,
+            next_step_time: 0.,
+            murphy_control: MurphyControl::None,
+            pending_action: Action::None,
+            solving: None,
+            solved: None,
         }
     }
 
-    pub fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame, tool_panel: &ToolPanel) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading(&self.heading);
+    /// Replaces the edited levels, e.g. with the ones loaded from LEVELS.DAT.
+    pub fn set_levels(&mut self, levels: Vec<Level>) {
+        self.histories = levels.iter().map(|_| History::default()).collect();
+        self.levels = levels;
+        self.stroke_start = None;
+        self.select_level(0);
+    }
+
+    /// Switches the edited level. Edits made to the previously selected level are kept.
+    pub fn select_level(&mut self, index: usize) {
+        self.finish_stroke();
+        self.selected_level_index = index;
+        self.highlight = vec![false; self.levels[index].play_area.len()];
+        self.tool_mode = None;
+        self.selection = None;
+        self.select_action = None;
+        self.stop_simulation();
+    }
+
+    /// The name of the level as shown in the level selection, e.g. "001 WARM UP".
+    fn level_name(&self, index: usize) -> String {
+        let title = self.levels[index].info.title();
+        let modified = if self.histories[index].is_modified() { "*" } else { "" };
+        format!("{:03} {}{}", index + 1, title.trim_matches(|c| c == '-' || c == ' '), modified)
+    }
+
+    /// Indicates whether any of the levels has been modified since they were last saved.
+    pub fn is_modified(&self) -> bool {
+        self.stroke_start.is_some() || self.histories.iter().any(History::is_modified)
+    }
+
+    /// Marks the levels as saved.
+    pub fn mark_saved(&mut self) {
+        self.finish_stroke();
+        for history in &mut self.histories {
+            history.mark_saved();
+        }
+    }
 
-            // TODO: get this from levels.dat
-            let levels = ["--- FIRST ---", "--- SECOND ---", "-- THIRD --"];
+    pub fn levels(&self) -> &[Level] {
+        &self.levels
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.levels[self.selected_level_index]
+    }
 
-            let cmb = ComboBox::from_label("level");
-            let cmb_res = cmb.show_index(ui, &mut self.selected_level_index, levels.len(), |i| levels[i].to_owned());
-            if cmb_res.changed() {
-                println!("{}", levels[self.selected_level_index]);
-                // TODO: switch to selected level
+    /// Replaces the selected level, e.g. with one imported from a .SP file, as one undoable step.
+    pub fn set_level(&mut self, level: Level) {
+        self.finish_stroke();
+        self.edit_level(|selected| *selected = level);
+        self.select_level(self.selected_level_index);
+    }
+
+    /// The selected tile, if exactly one tile is selected.
+    pub fn selected_tile_index(&self) -> Option<usize> {
+        self.selection
+            .filter(|rect| rect.width == 1 && rect.height == 1)
+            .map(|rect| self.level().index(rect.col, rect.row))
+    }
+
+    /// Selects the tile and scrolls it into view, e.g. the one a diagnostic is about.
+    pub fn select_tile(&mut self, index: usize) {
+        self.selection = Some(TileRect::from_corners(self.level(), index, index));
+        self.scroll_to_selected = true;
+    }
+
+    /// Edits the selected level, recording the changes as one undoable step.
+    pub fn edit_level<R>(&mut self, edit: impl FnOnce(&mut Level) -> R) -> R {
+        let before = self.level().clone();
+        let result = edit(&mut self.levels[self.selected_level_index]);
+        self.histories[self.selected_level_index].push(&before, &self.levels[self.selected_level_index]);
+        result
+    }
+
+    pub fn undo(&mut self) {
+        self.finish_stroke();
+        let index = self.selected_level_index;
+        if self.histories[index].undo(&mut self.levels[index]) {
+            self.cancel_tool();
+            self.drop_selection_outside_level();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        self.finish_stroke();
+        let index = self.selected_level_index;
+        if self.histories[index].redo(&mut self.levels[index]) {
+            self.cancel_tool();
+            self.drop_selection_outside_level();
+        }
+    }
+
+    /// Clears the selection if it doesn't fit in the level, e.g. after undoing a rotation.
+    fn drop_selection_outside_level(&mut self) {
+        let (width, height) = (self.level().width(), self.level().height());
+        if self.selection.is_some_and(|rect| rect.col + rect.width > width || rect.row + rect.height > height) {
+            self.selection = None;
+            self.select_action = None;
+        }
+    }
+
+    /// Mirrors or turns the selected tiles, or the whole level if nothing is selected.
+    /// Turning the whole level swaps its width and height, which is refused unless
+    /// `any_size` says the levels can be saved that way. Turning the selection is refused
+    /// if the turned tiles don't fit in the level.
+    pub fn transform(&mut self, transform: Transform, any_size: bool) -> Result<(), String> {
+        self.stop_simulation();
+        self.finish_stroke();
+        self.select_action = None;
+        match self.selection {
+            Some(rect) => {
+                let block = Block::copy(self.level(), rect).transformed(transform);
+                if rect.col + block.width() > self.level().width() || rect.row + block.height() > self.level().height() {
+                    return Err(String::from("The turned selection doesn't fit in the level. Move it away from the edge first."));
+                }
+                self.selection = self.edit_level(|level| {
+                    block::clear(level, rect);
+                    block.paste(level, rect.col as isize, rect.row as isize)
+                });
+            }
+            None => {
+                let (width, height) = (self.level().width(), self.level().height());
+                if transform == Transform::RotateClockwise && width != height && !any_size {
+                    return Err(format!("Turning the whole level makes it {}x{}, which can only be saved in .MPX and .TXT files. \
+                        Select the tiles to turn, or save the levels as .MPX first.", height, width));
+                }
+                self.edit_level(|level| *level = block::transform_level(level, transform));
+                self.cancel_tool();
             }
+        }
+        Ok(())
+    }
+
+    /// Records the freehand stroke being drawn, if any, as one undoable step.
+    fn finish_stroke(&mut self) {
+        if let Some(before) = self.stroke_start.take() {
+            self.histories[self.selected_level_index].push(&before, &self.levels[self.selected_level_index]);
+        }
+    }
 
-            let spacing = ui.spacing_mut();
-            spacing.item_spacing = vec2(0., 0.);
+    fn handle_undo_keys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return; // e.g. editing the title, which has undo of its own
+        }
+        let (undo, redo) = {
+            let input = ctx.input();
+            let command = input.modifiers.command;
+            (command && !input.modifiers.shift && input.key_pressed(Key::Z),
+             command && (input.key_pressed(Key::Y) || input.modifiers.shift && input.key_pressed(Key::Z)))
+        };
+        if undo {
+            self.undo();
+        }
+        else if redo {
+            self.redo();
+        }
+    }
 
-            let vlayout = Layout::top_down(Align::Min);
-            ui.with_layout(vlayout, |ui| {
-                match tool_panel.operating_mode() {
-                    OperatingMode::Draw => self.do_draw(ui, tool_panel, ctx),
-                    OperatingMode::Select => self.do_select(ui, tool_panel, ctx)
+    /// Handles Ctrl+C, Ctrl+X and Ctrl+V for copying, cutting and pasting the selected tiles,
+    /// and Escape for cancelling the paste or move, or clearing the selection.
+    fn handle_clipboard_keys(&mut self, ctx: &egui::Context, tool_panel: &mut ToolPanel) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let (copy, cut, paste, escape) = {
+            let input = ctx.input();
+            let command = input.modifiers.command;
+            (command && input.key_pressed(Key::C), command && input.key_pressed(Key::X),
+             command && input.key_pressed(Key::V), input.key_pressed(Key::Escape))
+        };
+        if let Some(rect) = self.selection.filter(|_| copy || cut) {
+            self.clipboard = Some(Block::copy(self.level(), rect));
+            if cut {
+                self.edit_level(|level| block::clear(level, rect));
+            }
+        }
+        if paste && self.clipboard.is_some() {
+            tool_panel.set_operating_mode(OperatingMode::Select);
+            self.select_action = Some(SelectAction::Paste { at: None });
+        }
+        if escape && self.select_action.take().is_none() {
+            self.selection = None;
+        }
+    }
+
+    /// Handles the T and R keys: T cycles through the variants of a tile, or toggles the line
+    /// mode while drawing a line, and R rotates a tile. They apply to the hovered tile, the
+    /// selected tile, or the selected tool, in that order.
+    fn handle_variant_keys(&mut self, ctx: &egui::Context, tool_panel: &mut ToolPanel) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let (toggle, rotate, drawing) = {
+            let input = ctx.input();
+            let plain = !input.modifiers.command && !input.modifiers.alt;
+            (plain && input.key_pressed(Key::T), plain && input.key_pressed(Key::R), input.pointer.any_down())
+        };
+        if let (true, Some(tool_mode @ ToolMode::Line { .. })) = (toggle, self.tool_mode) {
+            self.tool_mode = Some(tool_mode.toggle_line_mode());
+            return;
+        }
+        if !(toggle || rotate) || drawing {
+            return;
+        }
+        let selected_tile_index = match tool_panel.operating_mode() {
+            OperatingMode::Select => self.selected_tile_index(),
+            OperatingMode::Draw => None,
+        };
+        match (self.hovered_tile_index.or(selected_tile_index), toggle) {
+            (Some(index), true) => self.toggle_tile_variant(index),
+            (Some(index), false) => self.rotate_tile(index),
+            (None, true) => tool_panel.select_next_variant(),
+            (None, false) => tool_panel.select_rotated(),
+        }
+    }
+
+    /// Cycles the tile through its variants. Turning a port into a gravity port also gives
+    /// it a special port table entry.
+    fn toggle_tile_variant(&mut self, index: usize) {
+        self.edit_level(|level| {
+            let tile = level.play_area[index];
+            if tile.is_gravity_port() {
+                level.remove_special_port(index);
+            }
+            else if tile.to_gravity_port().is_some() {
+                level.set_special_port(SpecialPort::new(index)).ok(); // unless the table is full
+            }
+            else if matches!(tile, Tile::RamChip | Tile::RamLeft | Tile::RamRight | Tile::RamTop | Tile::RamBottom) {
+                Self::toggle_ram_chip(level, index);
+            }
+            else {
+                level.set_tile(index, tile.next_variant());
+            }
+        });
+    }
+
+    /// Cycles the RAM chip at `index` through the one-tile chip, the horizontal two-tile chip
+    /// and the vertical one, keeping the left or top half where the chip starts. The other
+    /// half is only placed on an empty tile, the kinds without room for it are skipped. A
+    /// half without the other half becomes a one-tile chip.
+    fn toggle_ram_chip(level: &mut Level, index: usize) {
+        let tile = level.play_area[index];
+        let partner = tile.ram_chip_partner()
+            .and_then(|(offset, partner)| level.offset_index(index, offset).filter(|&i| level.play_area[i] == partner));
+        let (start, partner) = match (tile, partner) {
+            (Tile::RamRight | Tile::RamBottom, Some(partner)) => (partner, Some(index)),
+            _ => (index, partner),
+        };
+        const KINDS: [Tile; 3] = [Tile::RamChip, Tile::RamLeft, Tile::RamTop];
+        let current = match (tile, partner) {
+            (Tile::RamChip, _) => 0,
+            (Tile::RamLeft | Tile::RamRight, Some(_)) => 1,
+            _ => 2,
+        };
+        for kind in [KINDS[(current + 1) % 3], KINDS[(current + 2) % 3]] {
+            let second = match kind.ram_chip_partner() {
+                Some((offset, second)) => match level.offset_index(start, offset) {
+                    Some(i) if level.play_area[i] == Tile::Empty || Some(i) == partner => Some((i, second)),
+                    _ => continue, // no room
+                },
+                None => None,
+            };
+            if let Some(partner) = partner {
+                level.set_tile(partner, Tile::Empty);
+            }
+            level.set_tile(start, kind);
+            if let Some((i, second)) = second {
+                level.set_tile(i, second);
+            }
+            return;
+        }
+    }
+
+    /// Turns the tile 90 degrees clockwise. The other half of a two-tile RAM chip turns
+    /// along with it, unless it would end up outside the level or on another tile.
+    fn rotate_tile(&mut self, index: usize) {
+        self.edit_level(|level| {
+            let tile = level.play_area[index];
+            let rotated = tile.rotated();
+            if let (Some((offset, partner)), Some((new_offset, new_partner))) = (tile.ram_chip_partner(), rotated.ram_chip_partner()) {
+                let old_index = level.offset_index(index, offset).filter(|&i| level.play_area[i] == partner);
+                let Some(new_index) = level.offset_index(index, new_offset)
+                    .filter(|&i| level.play_area[i] == Tile::Empty || Some(i) == old_index) else { return };
+                level.set_tile(index, rotated);
+                if let Some(old_index) = old_index {
+                    level.set_tile(old_index, Tile::Empty);
                 }
-            });
+                level.set_tile(new_index, new_partner);
+            }
+            else {
+                level.set_tile(index, rotated);
+            }
+            level.remove_stale_special_ports();
         });
     }
 
-    fn do_draw(&mut self, ui: &mut egui::Ui, tool_panel: &ToolPanel, ctx: &egui::Context) {
-        for row in 0..PLAY_AREA_HEIGHT {
-            let hlayout = Layout::left_to_right(Align::Min).with_main_wrap(false);
-            ui.with_layout(hlayout, |ui| {
-
-                let selected_tool_tile = tool_panel.selected_tool_tile();
-                for col in 0..PLAY_AREA_WIDTH {
-                    let is_delete = ui.input().pointer.secondary_down();
-                    let tile_index = index(col, row);
-                    let response = self.add_image_button_draw(tile_index, selected_tool_tile, is_delete, ctx, ui);
-
-                    // - toggle between variants: T
-                    //      * yellow, red, orange disk
-                    //      * port, gravity port
-                    //      * different walls
-                    //      * ram chips
-                    //      * base/bug
-                    //      * switch between line draw modes (HV/VH)
-                    // - rotate: R (applies to 2-piece ram chips, ports)
-                    //
-                    let ptr = &ui.input().pointer;
-                    if response.hovered() && (ptr.primary_down() || ptr.secondary_down()) {
-                        let mode = self.get_tool_mode(tile_index, selected_tool_tile, &ui.input());
-                        self.tool_mode = Some(mode);
-                        self.highlight.fill(false);
-                        match mode {
-                            ToolMode::Draw{tile}
-                                => self.play_area[tile_index] = tile,
-                            ToolMode::Line { tile: _, start, mode: LineMode::HorizontalFirst }
-                                => self.line_horizontal_first(start, tile_index),
-                            ToolMode::Line { tile: _, start, mode: LineMode::VerticalFirst }
-                                => self.line_vertical_first(start, tile_index),
-                            ToolMode::Rect { tile: _, start }
-                                => self.rect(start, tile_index),
-                            _ => {},
-                        }
-                    }
+    pub fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame, tool_panel: &mut ToolPanel) {
+        let editing = self.simulation.is_none();
+        if editing {
+            self.handle_undo_keys(ctx);
+            self.handle_clipboard_keys(ctx, tool_panel);
+        }
+        else if ctx.input().key_pressed(Key::Escape) {
+            self.stop_simulation();
+        }
+        if matches!(tool_panel.operating_mode(), OperatingMode::Draw) {
+            self.select_action = None;
+            self.selection = None;
+        }
+        self.hovered_tile_index = None;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading(&self.heading);
+
+            ui.horizontal(|ui| {
+                let mut selected_level_index = self.selected_level_index;
+                let cmb = ComboBox::from_label("level");
+                let cmb_res = cmb.show_index(ui, &mut selected_level_index, self.levels.len(), |i| self.level_name(i));
+                if cmb_res.changed() {
+                    self.select_level(selected_level_index);
                 }
+                ui.checkbox(&mut self.show_unreachable, "Shade unreachable")
+                    .on_hover_text("Infotrons, terminals and exits that Murphy can't get to");
+                ui.checkbox(&mut self.show_unstable, "Mark unstable")
+                    .on_hover_text("Things that fall or roll off as soon as the level starts, and enemies next to Murphy");
+                ui.separator();
+                ui.label(format!("Zoom {:.0}%", self.zoom * 100.))
+                    .on_hover_text("Ctrl+wheel to zoom, middle button or Space+drag to pan");
+            });
+            ui.horizontal(|ui| {
+                self.show_simulation_controls(ui);
+                self.show_solver_controls(ui);
+            });
+            self.update_unreachable();
+            self.run_simulation(ctx);
+            self.update_solver(ctx);
 
-                self.try_complete_tool(ui, selected_tool_tile);
+            let level_size = vec2(self.level().width() as f32, self.level().height() as f32) * TILE_SIZE;
+            if self.fit_pending {
+                let fit = ui.available_size() / level_size;
+                self.set_zoom(fit.x.min(fit.y));
+                self.fit_pending = false;
+            }
+            self.update_panning(&ui.input(), ui.available_rect_before_wrap());
+            let mut scroll_area = ScrollArea::both();
+            if let Some(offset) = self.scroll_offset.take() {
+                scroll_area = scroll_area.scroll_offset(offset);
+            }
+            let output = scroll_area.show(ui, |ui| {
+                // Sensing drags on the play area keeps drawing from scrolling large levels.
+                let (rect, response) = ui.allocate_exact_size(level_size * self.zoom, Sense::click_and_drag());
+                self.hovered_tile_index = self.tile_at(rect, response.hover_pos()).filter(|_| !self.panning);
+                match tool_panel.operating_mode() {
+                    _ if self.simulation.is_some() => self.do_simulate(ui, rect, ctx),
+                    OperatingMode::Draw => self.do_draw(ui, rect, tool_panel, ctx),
+                    OperatingMode::Select => self.do_select(ui, rect, tool_panel, ctx)
+                }
+                if self.simulation.is_none() {
+                    self.paint_unstable(ui, rect);
+                    self.paint_solution(ui, rect);
+                }
             });
+            self.update_zoom_and_pan(&ui.input(), output.inner_rect, output.state.offset);
+        });
+        if editing {
+            self.handle_variant_keys(ctx, tool_panel);
         }
     }
 
-    fn do_select(&mut self, ui: &mut egui::Ui, tool_panel: &ToolPanel, ctx: &egui::Context) {
-        for row in 0..PLAY_AREA_HEIGHT {
-            let hlayout = Layout::left_to_right(Align::Min).with_main_wrap(false);
-            ui.with_layout(hlayout, |ui| {
-                for col in 0..PLAY_AREA_WIDTH {
-                    let tile_index = index(col, row);
-                    let is_selected = match self.selected_tile_index {
-                        Some(x) => x == tile_index,
-                        None => false,
-                    };
-                    if self.add_image_button_select(tile_index, is_selected, ctx, ui).clicked() {
-                        self.selected_tile_index = (!is_selected).then(|| tile_index) ;
-                    }
+    /// Shows the buttons for playing the level out by the rules of the game. The simulation
+    /// runs on a copy of the level, which is shown until the simulation is stopped.
+    fn show_simulation_controls(&mut self, ui: &mut egui::Ui) {
+        let play_text = if self.simulation_running { "Pause" } else { "Play" };
+        if ui.button(play_text).on_hover_text("Simulate the falling objects, enemies and explosions").clicked() {
+            if self.simulation.is_none() {
+                self.start_simulation(MurphyControl::None);
+            }
+            self.simulation_running = !self.simulation_running;
+            self.next_step_time = ui.input().time + SIMULATION_STEP_SECONDS;
+        }
+        if ui.button("Step").clicked() {
+            if self.simulation.is_none() {
+                self.start_simulation(MurphyControl::None);
+            }
+            self.step_simulation();
+            self.simulation_running = false;
+        }
+        let playtest_hint = "Arrows move Murphy, Space+arrow snaps, Space drops a red disk, Escape goes back to editing";
+        if ui.button("Playtest").on_hover_text(playtest_hint).clicked() {
+            self.start_simulation(MurphyControl::Player { recorded: vec![] });
+            self.simulation_running = true;
+            self.next_step_time = ui.input().time + SIMULATION_STEP_SECONDS;
+        }
+        let demo = Demo::from_bytes(&self.level().demo);
+        let demo_button = ui.add_enabled(demo.is_ok(), Button::new("Play Demo"));
+        let demo_button = match &demo {
+            Ok(demo) => demo_button.on_hover_text(format!("{} steps", demo.actions.len())),
+            Err(_) if self.level().demo.is_empty() => demo_button.on_disabled_hover_text("The level has no demo"),
+            Err(e) => demo_button.on_disabled_hover_text(e.to_string()),
+        };
+        if let (true, Ok(demo)) = (demo_button.clicked(), demo) {
+            self.start_simulation(MurphyControl::Demo(demo.actions));
+            self.simulation_running = true;
+            self.next_step_time = ui.input().time + SIMULATION_STEP_SECONDS;
+        }
+        let remove = ui.add_enabled(self.simulation.is_none() && !self.level().demo.is_empty(), Button::new("Remove Demo"))
+            .on_hover_text("Remove the demo from the level, for example before saving to LEVELS.DAT, which can't hold it");
+        if remove.clicked() {
+            self.edit_level(|level| level.demo.clear());
+        }
+        if ui.add_enabled(self.simulation.is_some(), Button::new("Stop")).on_hover_text("Back to editing").clicked() {
+            self.stop_simulation();
+        }
+        if let MurphyControl::Player { recorded } = &self.murphy_control {
+            let attach = ui.add_enabled(!recorded.is_empty(), Button::new("Attach Demo"))
+                .on_hover_text("Store the moves of this playtest as the demo of the level, saved in .SP files");
+            if attach.clicked() {
+                let demo = Demo { level_number: (self.selected_level_index + 1) as u8, actions: recorded.clone() };
+                self.edit_level(|level| level.demo = demo.to_bytes());
+            }
+        }
+
+        let Some(simulation) = &self.simulation else { return };
+        ui.label(format!("Step {}", simulation.step_count()));
+        if self.murphy_control != MurphyControl::None {
+            ui.label(format!("Infotrons {}/{}", simulation.infotrons_collected(), simulation.infotrons_needed()));
+            ui.label(format!("Red disks {}", simulation.red_disks()));
+            if simulation.is_won() {
+                ui.colored_label(Color32::GREEN, "Level complete!");
+            }
+            else if simulation.is_murphy_dead() {
+                ui.colored_label(Color32::RED, "Murphy died");
+            }
+        }
+    }
+
+    fn start_simulation(&mut self, murphy_control: MurphyControl) {
+        self.simulation = Some(Simulation::new(self.level()));
+        self.murphy_control = murphy_control;
+        self.pending_action = Action::None;
+    }
+
+    fn stop_simulation(&mut self) {
+        self.simulation = None;
+        self.simulation_running = false;
+        self.murphy_control = MurphyControl::None;
+    }
+
+    /// Steps the simulation, with Murphy doing what the player or the demo says.
+    fn step_simulation(&mut self) {
+        let Some(simulation) = &mut self.simulation else { return };
+        let action = match &mut self.murphy_control {
+            MurphyControl::None => Action::None,
+            MurphyControl::Player { recorded } => {
+                let action = std::mem::replace(&mut self.pending_action, Action::None);
+                recorded.push(action);
+                action
+            }
+            MurphyControl::Demo(actions) => actions.get(simulation.step_count() as usize).copied().unwrap_or(Action::None),
+        };
+        simulation.step(action);
+    }
+
+    /// Steps the running simulation when it's time to. When playtesting, Murphy does what
+    /// the player chose last since the previous step, so that short key presses aren't lost.
+    /// Keys typed into a text field, e.g. the title, don't move him.
+    fn run_simulation(&mut self, ctx: &egui::Context) {
+        if matches!(self.murphy_control, MurphyControl::Player { .. }) && !ctx.wants_keyboard_input() {
+            let action = Self::read_action(&ctx.input());
+            if action != Action::None {
+                self.pending_action = action;
+            }
+        }
+        if self.simulation.is_none() || !self.simulation_running {
+            return;
+        }
+        let now = ctx.input().time;
+        if now >= self.next_step_time {
+            self.step_simulation();
+            self.next_step_time = now + SIMULATION_STEP_SECONDS;
+            let finished = self.simulation.as_ref().is_some_and(|s| s.is_won() || s.is_murphy_dead());
+            if finished && self.murphy_control != MurphyControl::None {
+                self.simulation_running = false;
+            }
+        }
+        ctx.request_repaint_after(Duration::from_secs_f64(self.next_step_time - now));
+    }
+
+    /// Shows the button for searching for a solution of the level, and the outcome of the
+    /// search while the level stays as it was.
+    fn show_solver_controls(&mut self, ui: &mut egui::Ui) {
+        ui.separator();
+        if self.solving.is_some() {
+            ui.add_enabled(false, Button::new("Solving..."));
+            ui.spinner();
+            return;
+        }
+        let limits = Limits::default();
+        let hint = format!("Search for the shortest way to complete the level, for at most {} seconds", limits.time.as_secs());
+        if ui.button("Solve").on_hover_text(hint).clicked() {
+            let level = self.level().clone();
+            let copy = level.clone();
+            self.solving = Some((self.selected_level_index, level, std::thread::spawn(move || solver::solve(&copy, limits))));
+        }
+        let Some(solved) = self.current_solution() else { return };
+        match &solved.solution {
+            Solution::Solved(actions) => {
+                ui.colored_label(Color32::GREEN, format!("Solved in {} steps", actions.len()));
+                if ui.button("Play Solution").clicked() {
+                    let actions = actions.clone();
+                    self.start_simulation(MurphyControl::Demo(actions));
+                    self.simulation_running = true;
+                    self.next_step_time = ui.input().time + SIMULATION_STEP_SECONDS;
                 }
-            });
+            }
+            Solution::Unsolvable => { ui.colored_label(Color32::RED, "Unsolvable"); }
+            Solution::OutOfBounds => { ui.colored_label(Color32::YELLOW, "No solution found within the limits"); }
         }
     }
-    
-    fn add_image_button_draw(&self, tile_index: usize, tool_tile: Tile, is_delete: bool, ctx: &egui::Context, ui: &mut egui::Ui) -> egui::Response {
-        let tile = if self.highlight[tile_index] && !is_delete { tool_tile } else { self.play_area[tile_index] };
-        let texture_id = self.images[tile].texture_id(ctx);
-        let mut btn = ImageButton::new(texture_id, vec2(32., 32.));
-        btn = btn.frame(false);
-        btn = btn.sense(Sense::hover());
-        if self.highlight[tile_index] {
-            let tint_color = if is_delete { hex_color!("#ff808080") } else { Color32::DARK_GRAY };
-            btn = btn.tint(tint_color);
+
+    /// Picks up the outcome of the search when it's done.
+    fn update_solver(&mut self, ctx: &egui::Context) {
+        if !self.solving.as_ref().is_some_and(|(_, _, handle)| handle.is_finished()) {
+            if self.solving.is_some() {
+                ctx.request_repaint_after(Duration::from_millis(100));
+            }
+            return;
+        }
+        let (index, level, handle) = self.solving.take().unwrap();
+        let solution = handle.join().unwrap_or(Solution::OutOfBounds);
+        let path = match &solution {
+            Solution::Solved(actions) => solver::murphy_path(&level, actions),
+            _ => vec![],
+        };
+        self.solved = Some(SolvedLevel { index, level, solution, path });
+    }
+
+    /// The solution found for the selected level, unless the level has changed since.
+    fn current_solution(&self) -> Option<&SolvedLevel> {
+        self.solved.as_ref().filter(|solved| {
+            let level = self.level();
+            solved.index == self.selected_level_index && solved.level.play_area == level.play_area && solved.level.info == level.info
+        })
+    }
+
+    /// Draws the way Murphy takes in the solution over the level.
+    fn paint_solution(&self, ui: &egui::Ui, rect: Rect) {
+        let Some(solved) = self.current_solution() else { return };
+        let centers: Vec<Pos2> = solved.path.iter().map(|&index| self.tile_rect(rect, index).center()).collect();
+        let stroke = Stroke::new(3. * self.zoom, SOLUTION_COLOR);
+        for segment in centers.windows(2) {
+            ui.painter().line_segment([segment[0], segment[1]], stroke);
+        }
+        if let (Some(&first), Some(&last)) = (centers.first(), centers.last()) {
+            ui.painter().circle_filled(first, 5. * self.zoom, SOLUTION_COLOR);
+            ui.painter().circle_stroke(last, 8. * self.zoom, stroke);
+        }
+    }
+
+    /// The action chosen with the arrow keys and Space.
+    fn read_action(input: &InputState) -> Action {
+        let keys = [
+            (Key::ArrowRight, Direction::Right),
+            (Key::ArrowDown, Direction::Down),
+            (Key::ArrowLeft, Direction::Left),
+            (Key::ArrowUp, Direction::Up),
+        ];
+        let direction = keys.iter().find(|(key, _)| input.key_down(*key)).map(|&(_, direction)| direction);
+        match (direction, input.key_down(Key::Space)) {
+            (Some(direction), false) => Action::Move(direction),
+            (Some(direction), true) => Action::Snap(direction),
+            (None, true) => Action::DropDisk,
+            (None, false) => Action::None,
+        }
+    }
+
+    /// Sets the size of the tiles relative to the tile images, within limits.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    /// Zooms so that the whole level fits in the window.
+    pub fn zoom_to_fit(&mut self) {
+        self.fit_pending = true;
+    }
+
+    /// Starts panning when the middle button, or the primary one with Space held down, is
+    /// pressed in the part of the screen showing the level, and stops when it's released.
+    fn update_panning(&mut self, input: &InputState, view: Rect) {
+        let ptr = &input.pointer;
+        if ptr.middle_down() || (input.key_down(Key::Space) && ptr.primary_down()) {
+            let pressed = ptr.button_clicked(PointerButton::Middle) || ptr.primary_clicked();
+            self.panning |= pressed && ptr.hover_pos().is_some_and(|pos| view.contains(pos));
+        }
+        else {
+            self.panning = false;
+        }
+    }
+
+    /// Zooms with Ctrl+wheel, keeping the point under the pointer in place, and scrolls
+    /// along with the pointer while panning. `view` is the part of the screen showing the
+    /// level, scrolled by `offset`.
+    fn update_zoom_and_pan(&mut self, input: &InputState, view: Rect, offset: Vec2) {
+        let ptr = &input.pointer;
+        let pointer_in_view = ptr.hover_pos().filter(|&pos| view.contains(pos));
+        if self.panning {
+            self.scroll_offset = Some(offset - ptr.delta());
+        }
+
+        let zoom_delta = input.zoom_delta();
+        if let (Some(pos), true) = (pointer_in_view, zoom_delta != 1.) {
+            let old_zoom = self.zoom;
+            self.set_zoom(self.zoom * zoom_delta);
+            let pointer_offset = pos - view.min;
+            self.scroll_offset = Some((offset + pointer_offset) * (self.zoom / old_zoom) - pointer_offset);
+        }
+    }
+
+    fn update_unreachable(&mut self) {
+        self.unreachable = vec![false; self.level().play_area.len()];
+        if self.show_unreachable {
+            for index in unreachable_targets(self.level()) {
+                self.unreachable[index] = true;
+            }
+        }
+    }
+
+    /// Outlines the things that move as soon as the level starts, and the enemies next to
+    /// Murphy.
+    fn paint_unstable(&self, ui: &egui::Ui, rect: Rect) {
+        if !self.show_unstable {
+            return;
+        }
+        let stroke = Stroke::new(2. * self.zoom, WARNING_COLOR);
+        for index in instabilities(self.level()).into_iter().filter_map(|diagnostic| diagnostic.tile_index) {
+            ui.painter().rect_stroke(self.tile_rect(rect, index).shrink(self.zoom), 0., stroke);
+        }
+    }
+
+    /// The size of the tiles on the screen.
+    fn tile_size(&self) -> f32 {
+        TILE_SIZE * self.zoom
+    }
+
+    /// The tile at the position, if any.
+    fn tile_at(&self, rect: Rect, pos: Option<Pos2>) -> Option<usize> {
+        let offset = (pos? - rect.min) / self.tile_size();
+        let (col, row) = (offset.x.floor(), offset.y.floor());
+        let (width, height) = (self.level().width(), self.level().height());
+        (col >= 0. && row >= 0. && (col as usize) < width && (row as usize) < height)
+            .then(|| self.level().index(col as usize, row as usize))
+    }
+
+    /// The rectangle of the tile on the screen, given the rectangle of the play area.
+    fn tile_rect(&self, rect: Rect, index: usize) -> Rect {
+        let (col, row) = self.level().col_row(index);
+        // From corner to corner, so that neighbouring tiles share their edges exactly.
+        let corner = |col: usize, row: usize| rect.min + vec2(col as f32, row as f32) * self.tile_size();
+        Rect::from_min_max(corner(col, row), corner(col + 1, row + 1))
+    }
+
+    /// Draws the play area as one mesh textured with the tile atlas. `tile_and_tint` gives
+    /// the tile to show at each index and the colour to tint it with.
+    fn paint_tiles(&self, ui: &egui::Ui, rect: Rect, ctx: &egui::Context, tile_and_tint: impl Fn(usize) -> (Tile, Color32)) {
+        let mut mesh = Mesh::with_texture(self.images.atlas_texture(ctx));
+        let clip_rect = ui.clip_rect();
+        for index in 0..self.level().play_area.len() {
+            let tile_rect = self.tile_rect(rect, index);
+            if clip_rect.intersects(tile_rect) {
+                let (tile, tint) = tile_and_tint(index);
+                mesh.add_rect_with_uv(tile_rect, self.images.atlas_uv(tile), tint);
+            }
+        }
+        ui.painter().add(Shape::mesh(mesh));
+    }
+
+    fn do_draw(&mut self, ui: &mut egui::Ui, rect: Rect, tool_panel: &ToolPanel, ctx: &egui::Context) {
+        let mut preview = vec![None; self.highlight.len()];
+        if let Some(brush) = self.tool_mode.and_then(|mode| mode.brush()) {
+            for (index, tile) in self.stamps(brush) {
+                preview[index] = Some(tile);
+            }
+        }
+        let is_delete = ui.input().pointer.secondary_down();
+        let play_area = &self.level().play_area;
+        self.paint_tiles(ui, rect, ctx, |index| match preview[index] {
+            Some(_) if is_delete => (play_area[index], hex_color!("#ff808080")),
+            Some(tile) => (tile, Color32::DARK_GRAY),
+            None if self.unreachable[index] => (play_area[index], UNREACHABLE_TINT),
+            None => (play_area[index], Color32::WHITE),
+        });
+
+        let any_button_down = ui.input().pointer.primary_down() || ui.input().pointer.secondary_down();
+        if let (Some(tile_index), true) = (self.hovered_tile_index, any_button_down) {
+            let mode = self.get_tool_mode(tile_index, tool_panel.selected_brush(), &ui.input());
+            self.tool_mode = Some(mode);
+            self.highlight.fill(false);
+            match mode {
+                ToolMode::Draw{brush}
+                    => self.draw(tile_index, brush),
+                ToolMode::Line { brush: _, start, mode: LineMode::HorizontalFirst }
+                    => self.line_horizontal_first(start, tile_index),
+                ToolMode::Line { brush: _, start, mode: LineMode::VerticalFirst }
+                    => self.line_vertical_first(start, tile_index),
+                ToolMode::Rect { brush: _, start }
+                    => self.rect(start, tile_index),
+                ToolMode::Fill { brush: _ }
+                    => self.fill(tile_index),
+                _ => {},
+            }
+        }
+        self.try_complete_tool(ui);
+
+        if !ui.input().pointer.any_down() {
+            self.finish_stroke();
+        }
+    }
+
+    /// Draws the simulated level, with the explosions and the sparking bugs highlighted.
+    fn do_simulate(&self, ui: &mut egui::Ui, rect: Rect, ctx: &egui::Context) {
+        let Some(simulation) = &self.simulation else { return };
+        let play_area = &simulation.level().play_area;
+        self.paint_tiles(ui, rect, ctx, |index| (play_area[index], Color32::WHITE));
+        for index in 0..play_area.len() {
+            let color = if simulation.is_exploding(index) { EXPLOSION_COLOR }
+                else if simulation.is_bug_active(index) { SPARK_COLOR }
+                else { continue };
+            ui.painter().rect_filled(self.tile_rect(rect, index), 0., color);
+        }
+    }
+
+    fn do_select(&mut self, ui: &mut egui::Ui, rect: Rect, _tool_panel: &ToolPanel, ctx: &egui::Context) {
+        let mut preview = vec![None; self.level().play_area.len()];
+        if let Some((block, col, row)) = self.floating_block() {
+            for (index, tile) in block.tiles_at(self.level(), col, row) {
+                preview[index] = Some(tile);
+            }
+        }
+        let play_area = &self.level().play_area;
+        self.paint_tiles(ui, rect, ctx, |index| {
+            let (col, row) = self.level().col_row(index);
+            match preview[index] {
+                Some(tile) => (tile, Color32::DARK_GRAY),
+                None if self.selection.is_some_and(|rect| rect.contains(col, row)) => (play_area[index], hex_color!("#80FF8080")),
+                None if self.unreachable[index] => (play_area[index], UNREACHABLE_TINT),
+                None => (play_area[index], Color32::WHITE),
+            }
+        });
+        if let Some(selection) = self.selection.filter(|_| self.scroll_to_selected) {
+            let index = self.level().index(selection.col, selection.row);
+            ui.scroll_to_rect(self.tile_rect(rect, index), Some(Align::Center));
+            self.scroll_to_selected = false;
+        }
+        self.update_selection(&ui.input());
+    }
+
+    /// The tiles following the pointer: the selected tiles being moved or the clipboard
+    /// being pasted, and the column and row of their top left corner.
+    fn floating_block(&self) -> Option<(Block, isize, isize)> {
+        let level = self.level();
+        match self.select_action? {
+            SelectAction::Move { from, to } => {
+                let rect = self.selection?;
+                let (col, row) = Self::moved(level, rect, from, to);
+                Some((Block::copy(level, rect), col, row))
+            }
+            SelectAction::Paste { at } => {
+                let (col, row) = level.col_row(at?);
+                Some((self.clipboard.clone()?, col as isize, row as isize))
+            }
+            SelectAction::Select { .. } => None,
+        }
+    }
+
+    /// The top left corner of the rectangle when moved by the distance between the tiles
+    /// at `from` and `to`.
+    fn moved(level: &Level, rect: TileRect, from: usize, to: usize) -> (isize, isize) {
+        let (from_col, from_row) = level.col_row(from);
+        let (to_col, to_row) = level.col_row(to);
+        (rect.col as isize + to_col as isize - from_col as isize, rect.row as isize + to_row as isize - from_row as isize)
+    }
+
+    /// Drags out the selection, moves the selected tiles or pastes the clipboard, depending
+    /// on where the primary button was pressed.
+    fn update_selection(&mut self, input: &InputState) {
+        let hovered = self.hovered_tile_index;
+        let pressed = input.pointer.primary_clicked();
+        let down = input.pointer.primary_down();
+        match self.select_action {
+            Some(SelectAction::Paste { at }) => {
+                self.select_action = match hovered {
+                    Some(index) if pressed => {
+                        self.paste(index);
+                        None
+                    }
+                    _ if input.pointer.button_clicked(PointerButton::Secondary) => None,
+                    _ => Some(SelectAction::Paste { at: hovered.or(at) }),
+                };
+            }
+            Some(SelectAction::Select { start }) => {
+                if let Some(index) = hovered {
+                    self.selection = Some(TileRect::from_corners(self.level(), start, index));
+                }
+                if !down {
+                    self.select_action = None;
+                }
+            }
+            Some(SelectAction::Move { from, to }) => {
+                let to = hovered.unwrap_or(to);
+                if down {
+                    self.select_action = Some(SelectAction::Move { from, to });
+                }
+                else {
+                    self.select_action = None;
+                    self.finish_move(from, to);
+                }
+            }
+            None => {
+                if let Some(index) = hovered.filter(|_| pressed) {
+                    let (col, row) = self.level().col_row(index);
+                    if self.selection.is_some_and(|rect| rect.contains(col, row)) {
+                        self.select_action = Some(SelectAction::Move { from: index, to: index });
+                    }
+                    else {
+                        self.selection = Some(TileRect::from_corners(self.level(), index, index));
+                        self.select_action = Some(SelectAction::Select { start: index });
+                    }
+                }
+            }
         }
-        ui.add(btn)
     }
 
-    fn add_image_button_select(&self, tile_index: usize, is_selected: bool, ctx: &egui::Context, ui: &mut egui::Ui) -> egui::Response {
-        let tile = self.play_area[tile_index];
-        let texture_id = self.images[tile].texture_id(ctx);
-        let mut btn = ImageButton::new(texture_id, vec2(32., 32.));
-        btn = btn.frame(false);
-        if is_selected {
-            btn = btn.tint(hex_color!("#80FF8080"));
+    /// Moves the selected tiles by the distance between the tiles at `from` and `to`. If
+    /// they weren't moved, the click selects just the clicked tile, or clears the selection
+    /// if that was the only tile selected.
+    fn finish_move(&mut self, from: usize, to: usize) {
+        let Some(rect) = self.selection else { return };
+        if from == to {
+            let clicked = TileRect::from_corners(self.level(), from, from);
+            self.selection = (rect != clicked).then_some(clicked);
+            return;
         }
-        ui.add(btn)
+        let (col, row) = Self::moved(self.level(), rect, from, to);
+        self.selection = self.edit_level(|level| {
+            let block = Block::copy(level, rect);
+            block::clear(level, rect);
+            block.paste(level, col, row)
+        });
     }
 
-    fn try_complete_tool(&mut self, ui: &mut egui::Ui, selected_tool: Tile) {
-        if let Some(_) = self.tool_mode {
+    /// Pastes the clipboard with its top left corner at the tile, and selects the pasted tiles.
+    fn paste(&mut self, index: usize) {
+        let Some(block) = self.clipboard.clone() else { return };
+        let (col, row) = self.level().col_row(index);
+        self.selection = self.edit_level(|level| block.paste(level, col as isize, row as isize));
+    }
+    
+    fn try_complete_tool(&mut self, ui: &mut egui::Ui) {
+        if let Some(tool_mode) = self.tool_mode {
             let input = ui.input();
             let modifiers = &input.modifiers;
             let ptr = &input.pointer;
             let primary_released = || self.ptr_primary && !ptr.primary_down();
             let secondary_released = || self.ptr_secondary && !ptr.secondary_down();
 
-            if (modifiers.shift || modifiers.ctrl) && primary_released() {
-                self.commit_draw(selected_tool);
-            }
-            else if (modifiers.shift || modifiers.ctrl) && secondary_released() {
-                self.commit_delete();
+            let any_modifier = modifiers.shift || modifiers.ctrl || modifiers.alt;
+            if any_modifier && (primary_released() || secondary_released()) {
+                match tool_mode.brush() {
+                    Some(brush) => self.commit_draw(brush),
+                    None => self.cancel_tool(),
+                }
             }
-            else if !modifiers.ctrl && !modifiers.shift {
+            else if !any_modifier {
                 self.cancel_tool();
             }
     
@@ -219,90 +1065,166 @@ impl EditorPanel {
     }
 
     fn cancel_tool(&mut self) {
-        println!("Canceling the tool");
-        self.highlight.fill(false);
+        self.highlight = vec![false; self.level().play_area.len()];
         self.tool_mode = None;
     }
 
-    fn commit_delete(&mut self) {
-        self.commit_draw(Tile::Empty);
+    /// Stamps the brush at the tile, unless the stroke has already drawn over it, so that
+    /// dragging a two-tile brush places whole RAM chips side by side.
+    fn draw(&mut self, tile_index: usize, brush: Brush) {
+        let level = &mut self.levels[self.selected_level_index];
+        if self.stroke_start.is_none() {
+            self.stroke_start = Some(level.clone());
+            self.stroke_covered = vec![false; level.play_area.len()];
+        }
+        if self.stroke_covered[tile_index] {
+            return;
+        }
+        for (index, tile) in brush.tiles(level, tile_index) {
+            level.set_tile(index, tile);
+            self.stroke_covered[index] = true;
+        }
+        level.remove_stale_special_ports();
     }
 
-    fn commit_draw(&mut self, sel_tool: Tile) {
-        self.highlight.iter_mut().enumerate().filter(|(_, &mut x)| x).for_each(|(i, y)| {
-            self.play_area[i] = sel_tool;
-            *y = false;
-        });
+    /// The tiles placed by stamping the brush over the highlighted tiles, row by row,
+    /// skipping the ones covered by an earlier stamp. A two-tile brush only stamps where it
+    /// fits in the highlighted tiles, the tiles left over get one-tile RAM chips.
+    fn stamps(&self, brush: Brush) -> Vec<(usize, Tile)> {
+        let mut covered = vec![false; self.highlight.len()];
+        let mut stamps = vec![];
+        for index in (0..self.highlight.len()).filter(|&i| self.highlight[i]) {
+            if covered[index] {
+                continue;
+            }
+            let mut tiles = brush.tiles(self.level(), index);
+            if tiles.is_empty() || tiles.iter().any(|&(i, _)| !self.highlight[i] || covered[i]) {
+                tiles = vec![(index, Tile::RamChip)];
+            }
+            for &(i, _) in &tiles {
+                covered[i] = true;
+            }
+            stamps.extend(tiles);
+        }
+        stamps
+    }
+
+    fn commit_draw(&mut self, brush: Brush) {
+        let stamps = self.stamps(brush);
+        let level = &mut self.levels[self.selected_level_index];
+        let before = level.clone();
+        for (index, tile) in stamps {
+            level.set_tile(index, tile);
+        }
+        self.highlight.fill(false);
+        level.remove_stale_special_ports();
+        self.histories[self.selected_level_index].push(&before, level);
         self.tool_mode = None;
     }
 
     fn line_horizontal_first(&mut self, start: usize, tile_index: usize) {
-        let (start_x, start_y) = col_row(start);
-        let (end_x, end_y) = col_row(tile_index);
+        let level = &self.levels[self.selected_level_index];
+        let (start_x, start_y) = level.col_row(start);
+        let (end_x, end_y) = level.col_row(tile_index);
         let (min_x, max_x) = minmax(start_x, end_x);
         let (min_y, max_y) = minmax(start_y, end_y);
-        let h_start = index(min_x, start_y);
-        let h_end = index(max_x, start_y);
-        self.highlight[h_start..h_end].fill(true);
-        let v_start = index(end_x, min_y);
-        let v_end = index(end_x, max_y);
-        for x in self.highlight[v_start..=v_end].iter_mut().step_by(PLAY_AREA_WIDTH) {
+        let h_start = level.index(min_x, start_y);
+        let h_end = level.index(max_x, start_y);
+        self.highlight[h_start..=h_end].fill(true);
+        let v_start = level.index(end_x, min_y);
+        let v_end = level.index(end_x, max_y);
+        for x in self.highlight[v_start..=v_end].iter_mut().step_by(level.width()) {
             *x = true;
         }
     }
 
     fn line_vertical_first(&mut self, start: usize, tile_index: usize) {
-        let (start_x, start_y) = col_row(start);
-        let (end_x, end_y) = col_row(tile_index);
+        let level = &self.levels[self.selected_level_index];
+        let (start_x, start_y) = level.col_row(start);
+        let (end_x, end_y) = level.col_row(tile_index);
         let (min_x, max_x) = minmax(start_x, end_x);
         let (min_y, max_y) = minmax(start_y, end_y);
-        let h_start = index(min_x, end_y);
-        let h_end = index(max_x, end_y);
-        self.highlight[h_start..h_end].fill(true);
-        let v_start = index(start_x, min_y);
-        let v_end = index(start_x, max_y);
-        for x in self.highlight[v_start..=v_end].iter_mut().step_by(PLAY_AREA_WIDTH) {
+        let h_start = level.index(min_x, end_y);
+        let h_end = level.index(max_x, end_y);
+        self.highlight[h_start..=h_end].fill(true);
+        let v_start = level.index(start_x, min_y);
+        let v_end = level.index(start_x, max_y);
+        for x in self.highlight[v_start..=v_end].iter_mut().step_by(level.width()) {
             *x = true;
         }
     }
 
+    /// Highlights the tiles connected to the one at `tile_index` through tiles of the same kind.
+    fn fill(&mut self, tile_index: usize) {
+        for index in self.level().connected_tiles(tile_index) {
+            self.highlight[index] = true;
+        }
+    }
+
     fn rect(&mut self, start: usize, end: usize) {
-        let (start_x, start_y) = col_row(start);
-        let (end_x, end_y) = col_row(end);
+        let level = &self.levels[self.selected_level_index];
+        let (start_x, start_y) = level.col_row(start);
+        let (end_x, end_y) = level.col_row(end);
         let (start_x, end_x) = minmax(start_x, end_x);
         let (start_y, end_y) = minmax(start_y, end_y);
 
         for row in start_y..=end_y {
-            let start = index(start_x, row);
-            let end = index(end_x, row);
+            let start = level.index(start_x, row);
+            let end = level.index(end_x, row);
             self.highlight[start..=end].fill(true);
         }
     }
 
-    fn get_tool_mode(&self, start_tile_index: usize, selected_tool_tile: Tile, input: &InputState) -> ToolMode {
+    fn get_tool_mode(&self, start_tile_index: usize, selected_brush: Brush, input: &InputState) -> ToolMode {
         // shift = line
         // ctrl = rect
+        // alt = fill
         // primary = draw
         // secondary = delete
-        let tile = if input.pointer.primary_down() { selected_tool_tile } else { Tile::Empty };
+        let brush = if input.pointer.primary_down() { selected_brush } else { Brush::new(Tile::Empty) };
         let modifiers = &input.modifiers;
         match self.tool_mode {
-            Some(tool_mode) => Self::toggle_line_mode(tool_mode, input.key_released(Key::T)),
-            None => match (modifiers.shift, modifiers.ctrl) {
-                (false, false) => ToolMode::Draw { tile },
-                (true, false) => ToolMode::Line { tile, start: start_tile_index, mode: LineMode::HorizontalFirst },
-                (false, true) => ToolMode::Rect { tile, start: start_tile_index },
-                (true, true) => ToolMode::Nop,
+            Some(tool_mode) => tool_mode,
+            None => match (modifiers.shift, modifiers.ctrl, modifiers.alt) {
+                (false, false, false) => ToolMode::Draw { brush },
+                (true, false, false) => ToolMode::Line { brush, start: start_tile_index, mode: LineMode::HorizontalFirst },
+                (false, true, false) => ToolMode::Rect { brush, start: start_tile_index },
+                (false, false, true) => ToolMode::Fill { brush },
+                _ => ToolMode::Nop,
             }
         }
     }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level_2x2(tiles: &[Tile]) -> Level {
+        let mut level = Level::new(2, 2);
+        level.play_area.copy_from_slice(tiles);
+        level
+    }
 
-    fn toggle_line_mode(tool_mode : ToolMode, toggle: bool) -> ToolMode {
-        if toggle {
-           tool_mode.toggle_line_mode()
-        }
-        else {
-            tool_mode
-        }
+    #[test]
+    fn ram_chip_kinds() {
+        use Tile::*;
+        let mut level = level_2x2(&[RamChip, Empty, Empty, Empty]);
+        EditorPanel::toggle_ram_chip(&mut level, 0);
+        assert_eq!(level.play_area, [RamLeft, RamRight, Empty, Empty]);
+        EditorPanel::toggle_ram_chip(&mut level, 1);
+        assert_eq!(level.play_area, [RamTop, Empty, RamBottom, Empty]);
+        EditorPanel::toggle_ram_chip(&mut level, 2);
+        assert_eq!(level.play_area, [RamChip, Empty, Empty, Empty]);
+    }
+
+    #[test]
+    fn ram_chip_kinds_without_room() {
+        use Tile::*;
+        let mut level = level_2x2(&[RamChip, Murphy, Empty, Empty]);
+        EditorPanel::toggle_ram_chip(&mut level, 0);
+        assert_eq!(level.play_area, [RamTop, Murphy, RamBottom, Empty]);
+        let mut level = level_2x2(&[RamChip, Murphy, Murphy, Empty]);
+        EditorPanel::toggle_ram_chip(&mut level, 0);
+        assert_eq!(level.play_area, [RamChip, Murphy, Murphy, Empty]);
     }
-}
\ No newline at end of file
+}
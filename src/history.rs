@@ -0,0 +1,220 @@
+// Undo and redo of the changes made to a level.
+
+use crate::level::{Level, LevelInfo};
+use crate::tile::Tile;
+
+/// A change of a level.
+enum Change {
+    /// The tiles that changed, and the info block and the demo before and after the change.
+    Edit {
+        tiles: Vec<(usize, Tile, Tile)>,
+        info: Option<(LevelInfo, LevelInfo)>,
+        demo: Option<(Vec<u8>, Vec<u8>)>,
+    },
+    /// The level before and after a change of its size, e.g. by turning it.
+    Resize(Level, Level),
+}
+
+impl Change {
+    fn new(before: &Level, after: &Level) -> Option<Self> {
+        if (before.width(), before.height()) != (after.width(), after.height()) {
+            return Some(Self::Resize(before.clone(), after.clone()));
+        }
+        let tiles: Vec<(usize, Tile, Tile)> = before.play_area.iter().zip(&after.play_area).enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(i, (&old, &new))| (i, old, new))
+            .collect();
+        let info = (before.info != after.info).then(|| (before.info.clone(), after.info.clone()));
+        let demo = (before.demo != after.demo).then(|| (before.demo.clone(), after.demo.clone()));
+        (!tiles.is_empty() || info.is_some() || demo.is_some()).then_some(Self::Edit { tiles, info, demo })
+    }
+
+    fn undo(&self, level: &mut Level) {
+        match self {
+            Self::Edit { tiles, info, demo } => {
+                for &(i, old, _) in tiles {
+                    level.play_area[i] = old;
+                }
+                if let Some((old, _)) = info {
+                    level.info = old.clone();
+                }
+                if let Some((old, _)) = demo {
+                    level.demo = old.clone();
+                }
+            }
+            Self::Resize(old, _) => *level = old.clone(),
+        }
+    }
+
+    fn redo(&self, level: &mut Level) {
+        match self {
+            Self::Edit { tiles, info, demo } => {
+                for &(i, _, new) in tiles {
+                    level.play_area[i] = new;
+                }
+                if let Some((_, new)) = info {
+                    level.info = new.clone();
+                }
+                if let Some((_, new)) = demo {
+                    level.demo = new.clone();
+                }
+            }
+            Self::Resize(_, new) => *level = new.clone(),
+        }
+    }
+}
+
+/// The undo and redo stacks of a single level.
+pub struct History {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+    /// Number of changes on the undo stack when the level was last saved, or None if the
+    /// saved state can no longer be reached by undo and redo.
+    saved: Option<usize>,
+}
+
+impl History {
+    /// Indicates whether the level differs from the one last saved.
+    pub fn is_modified(&self) -> bool {
+        self.saved != Some(self.undo.len())
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.undo.len());
+    }
+
+    /// Records the change from `before` to `after` as one undoable step. Nothing is recorded
+    /// if the level didn't change.
+    pub fn push(&mut self, before: &Level, after: &Level) {
+        if let Some(change) = Change::new(before, after) {
+            if self.saved.is_some_and(|saved| saved > self.undo.len()) {
+                self.saved = None; // the saved state was on the redo stack
+            }
+            self.undo.push(change);
+            self.redo.clear();
+        }
+    }
+
+    /// Reverts the latest change. Returns false if there was nothing to undo.
+    pub fn undo(&mut self, level: &mut Level) -> bool {
+        match self.undo.pop() {
+            Some(change) => {
+                change.undo(level);
+                self.redo.push(change);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reapplies the latest undone change. Returns false if there was nothing to redo.
+    pub fn redo(&mut self, level: &mut Level) -> bool {
+        match self.redo.pop() {
+            Some(change) => {
+                change.redo(level);
+                self.undo.push(change);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo: vec![],
+            redo: vec![],
+            saved: Some(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The level with the tile placed at `index`.
+    fn with_tile(level: &Level, index: usize, tile: Tile) -> Level {
+        let mut level = level.clone();
+        level.play_area[index] = tile;
+        level
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut history = History::default();
+        let start = Level::default();
+        let first = with_tile(&start, 61, Tile::Base);
+        let second = with_tile(&first, 62, Tile::Zonk);
+        history.push(&start, &first);
+        history.push(&first, &second);
+
+        let mut level = second.clone();
+        assert!(history.undo(&mut level));
+        assert_eq!(level.play_area, first.play_area);
+        assert!(history.undo(&mut level));
+        assert_eq!(level.play_area, start.play_area);
+        assert!(!history.undo(&mut level));
+        assert!(history.redo(&mut level));
+        assert!(history.redo(&mut level));
+        assert_eq!(level.play_area, second.play_area);
+        assert!(!history.redo(&mut level));
+    }
+
+    #[test]
+    fn unchanged_level_isnt_recorded() {
+        let mut history = History::default();
+        let level = Level::default();
+        history.push(&level, &level);
+        assert!(!history.is_modified());
+        assert!(!history.undo(&mut level.clone()));
+    }
+
+    #[test]
+    fn modified_since_saved() {
+        let mut history = History::default();
+        let start = Level::default();
+        let first = with_tile(&start, 61, Tile::Base);
+        let mut level = first.clone();
+        assert!(!history.is_modified());
+        history.push(&start, &first);
+        assert!(history.is_modified());
+        history.mark_saved();
+        assert!(!history.is_modified());
+
+        history.undo(&mut level);
+        assert!(history.is_modified());
+        history.redo(&mut level);
+        assert!(!history.is_modified());
+    }
+
+    #[test]
+    fn saved_state_lost_by_new_change() {
+        let mut history = History::default();
+        let start = Level::default();
+        let first = with_tile(&start, 61, Tile::Base);
+        let mut level = first.clone();
+        history.push(&start, &first);
+        history.mark_saved();
+        history.undo(&mut level);
+        // The saved state is on the redo stack, which the new change clears.
+        history.push(&level.clone(), &with_tile(&level, 62, Tile::Zonk));
+        assert!(history.is_modified());
+        history.undo(&mut level);
+        assert!(history.is_modified());
+    }
+
+    #[test]
+    fn resize() {
+        let mut history = History::default();
+        let start = Level::default();
+        let turned = Level::new(start.height(), start.width());
+        let mut level = turned.clone();
+        history.push(&start, &turned);
+        history.undo(&mut level);
+        assert_eq!((level.width(), level.height()), (start.width(), start.height()));
+        history.redo(&mut level);
+        assert_eq!((level.width(), level.height()), (turned.width(), turned.height()));
+    }
+}
@@ -1,66 +1,96 @@
-use std::{ops::Index, rc::Rc};
+use std::{cell::OnceCell, ops::Index, rc::Rc};
 
+use egui::{pos2, vec2, Color32, ColorImage, Rect, TextureHandle, TextureId};
 use egui_extras::RetainedImage;
 
-use crate::tool_panel::Tile;
+use crate::tile::{Tile, TILE_IMAGES, TILE_IMAGE_BYTES};
 
-pub const TILE_IMAGES:[&str; 40] = [
-    "empty.png",
-    "zonk.png",
-    "base.png",
-    "murphy.png",
-    "info.png",
-    "ram.png",
-    "hw8.png",
-    "exit.png",
-    "disk-orange.png",
-    "port-lr.png",
-    "port-ud.png",
-    "port-rl.png",
-    "port-du.png",
-    "gport-lr.png",
-    "gport-ud.png",
-    "gport-rl.png",
-    "gport-du.png",
-    "ssnak.png",
-    "disk-yellow.png",
-    "terminal.png",
-    "disk-red.png",
-    "port-v.png",
-    "port-h.png",
-    "port-x.png",
-    "electron.png",
-    "bug.png",
-    "ramh.png",
-    "ramright.png",
-    "hw1.png",
-    "hw2.png",
-    "hw3.png",
-    "hw4.png",
-    "hw5.png",
-    "hw6.png",
-    "hw7.png",
-    "hw8.png",
-    "hw9.png",
-    "hw10.png",
-    "ramv.png",
-    "rambottom.png",
-];
+/// Size of the tiles in the images, in pixels.
+const TILE_SIZE: usize = 32;
 
+/// Number of tiles on each row of the atlas.
+const ATLAS_COLUMNS: usize = 8;
+
+/// The images of the tiles, loaded once and shared by the panels.
 pub(crate) struct Images {
-    images: [Rc<RetainedImage>; 40]
+    images: [Rc<RetainedImage>; 40],
+    unknown: Rc<RetainedImage>,
+    /// The images of all the tiles in one image, with the unknown tile last.
+    atlas_image: ColorImage,
+    atlas: OnceCell<TextureHandle>,
 }
 
 impl Images {
     pub(crate) fn new() -> Self {
-        let images = TILE_IMAGES.map(|name| {
-            let image_path = format!("img/{}", name);
-            let image_bytes = std::fs::read(image_path).unwrap();
-            Rc::new(RetainedImage::from_image_bytes(format!("{:?}", name), &image_bytes).unwrap())
+        let tile_images: [ColorImage; 40] = std::array::from_fn(|i| {
+            egui_extras::image::load_image_bytes(TILE_IMAGE_BYTES[i]).unwrap_or_else(|e| {
+                eprintln!("Failed to decode {}: {}", TILE_IMAGES[i], e);
+                Self::placeholder_image()
+            })
         });
+        let unknown = Self::unknown_image();
+        let atlas_image = Self::atlas_image(&tile_images.iter().chain([&unknown]).collect::<Vec<_>>());
         Self {
-            images
+            images: std::array::from_fn(|i| Rc::new(RetainedImage::from_color_image(TILE_IMAGES[i], tile_images[i].clone()))),
+            unknown: Rc::new(RetainedImage::from_color_image("unknown", unknown)),
+            atlas_image,
+            atlas: OnceCell::new(),
+        }
+    }
+
+    /// Image for the tiles not known by the editor: a magenta square with a cross, so that
+    /// they stand out from the rest of the level.
+    fn unknown_image() -> ColorImage {
+        Self::crossed_square(Color32::from_rgb(0x60, 0x00, 0x60), Color32::from_rgb(0xff, 0x00, 0xff))
+    }
+
+    /// Image for the tiles whose image couldn't be decoded: a grey square with a red cross.
+    fn placeholder_image() -> ColorImage {
+        Self::crossed_square(Color32::from_gray(0x40), Color32::from_rgb(0xff, 0x40, 0x40))
+    }
+
+    /// A tile-sized square with a border and a cross in the foreground colour.
+    fn crossed_square(background: Color32, foreground: Color32) -> ColorImage {
+        const SIZE: usize = TILE_SIZE;
+        let mut image = ColorImage::new([SIZE, SIZE], background);
+        for i in 0..SIZE {
+            for (x, y) in [(i, 0), (i, SIZE - 1), (0, i), (SIZE - 1, i), (i, i), (SIZE - 1 - i, i)] {
+                image[(x, y)] = foreground;
+            }
         }
+        image
+    }
+
+    /// Puts the images in a grid of tiles. Of the images larger than a tile, like the whole
+    /// RAM chips shown in the tool panel, only the top left tile is used.
+    fn atlas_image(images: &[&ColorImage]) -> ColorImage {
+        let rows = images.len().div_ceil(ATLAS_COLUMNS);
+        let mut atlas = ColorImage::new([ATLAS_COLUMNS * TILE_SIZE, rows * TILE_SIZE], Color32::TRANSPARENT);
+        for (i, image) in images.iter().enumerate() {
+            let (left, top) = (i % ATLAS_COLUMNS * TILE_SIZE, i / ATLAS_COLUMNS * TILE_SIZE);
+            for y in 0..TILE_SIZE.min(image.size[1]) {
+                for x in 0..TILE_SIZE.min(image.size[0]) {
+                    atlas[(left + x, top + y)] = image[(x, y)];
+                }
+            }
+        }
+        atlas
+    }
+
+    /// The texture with the images of all the tiles, for drawing the play area in one go.
+    pub(crate) fn atlas_texture(&self, ctx: &egui::Context) -> TextureId {
+        self.atlas.get_or_init(|| ctx.load_texture("tile atlas", self.atlas_image.clone(), egui::TextureFilter::Nearest)).id()
+    }
+
+    /// The texture coordinates of the image of the tile in the atlas.
+    pub(crate) fn atlas_uv(&self, tile: Tile) -> Rect {
+        let i = match tile {
+            Tile::Unknown(_) => self.images.len(),
+            known => known.byte() as usize,
+        };
+        let [width, height] = self.atlas_image.size;
+        let (u, v) = ((i % ATLAS_COLUMNS * TILE_SIZE) as f32 / width as f32, (i / ATLAS_COLUMNS * TILE_SIZE) as f32 / height as f32);
+        Rect::from_min_size(pos2(u, v), vec2(TILE_SIZE as f32 / width as f32, TILE_SIZE as f32 / height as f32))
     }
 
     // pub(crate) fn name_of(tile: Tile) -> &'static str {
@@ -72,6 +102,9 @@ impl Index<Tile> for Images {
     type Output = Rc<RetainedImage>;
 
     fn index(&self, index: Tile) -> &Self::Output {
-        &self.images[index as usize]
+        match index {
+            Tile::Unknown(_) => &self.unknown,
+            known => &self.images[known.byte() as usize],
+        }
     }
 }
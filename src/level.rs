@@ -0,0 +1,427 @@
+// A single Supaplex level: the play area, one byte per tile, followed by the info block
+// containing the title, gravity, special ports etc. The levels in LEVELS.DAT and .SP files
+// are always 60x24 tiles, Megaplex .MPX files can contain levels of any size.
+
+use std::fmt;
+
+use crate::tile::Tile;
+
+// Dimensions of the levels in the original game.
+pub const PLAY_AREA_WIDTH: usize = 60;
+pub const PLAY_AREA_HEIGHT: usize = 24;
+pub const PLAY_AREA_SIZE: usize = PLAY_AREA_WIDTH * PLAY_AREA_HEIGHT;
+pub const INFO_SIZE: usize = 96;
+pub const LEVEL_SIZE: usize = PLAY_AREA_SIZE + INFO_SIZE;
+
+pub const TITLE_LENGTH: usize = 23;
+
+/// Characters that the game can display in level titles.
+pub const TITLE_CHARACTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 !\"'(),-./:?";
+
+// Offsets of the fields within the info block.
+pub(crate) const GRAVITY_OFFSET: usize = 4;
+pub(crate) const VERSION_OFFSET: usize = 5;
+pub(crate) const TITLE_OFFSET: usize = 6;
+pub(crate) const FREEZE_ZONKS_OFFSET: usize = 29;
+pub(crate) const INFOTRONS_NEEDED_OFFSET: usize = 30;
+pub(crate) const SPECIAL_PORT_COUNT_OFFSET: usize = 31;
+pub(crate) const SPECIAL_PORTS_OFFSET: usize = 32;
+pub(crate) const SPECIAL_PORT_SIZE: usize = 6;
+
+/// Maximum number of special (gravity) ports in a level.
+pub const MAX_SPECIAL_PORTS: usize = 10;
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(std::io::Error),
+    InvalidSize(usize),
+    /// A file that has to contain a fixed number of levels is given another number.
+    WrongLevelCount { expected: usize, found: usize },
+    InvalidTitle(String),
+    TooManySpecialPorts,
+    NotAPort(usize),
+    InvalidDimensions { width: usize, height: usize },
+    InvalidMpxHeader,
+    Parse { line: usize, message: String },
+    /// A demo that can't be read, with the offset of the offending byte.
+    InvalidDemo(usize),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::InvalidSize(size) => write!(f, "invalid file size: {} bytes", size),
+            Self::WrongLevelCount { expected, found } => write!(f, "expected {} levels, found {}", expected, found),
+            Self::InvalidTitle(title) => write!(f, "invalid title {:?}: at most {} characters of {:?} are allowed", title, TITLE_LENGTH, TITLE_CHARACTERS),
+            Self::TooManySpecialPorts => write!(f, "a level can have at most {} special ports", MAX_SPECIAL_PORTS),
+            Self::NotAPort(index) => write!(f, "tile {} is not a one-way port", index),
+            Self::InvalidDimensions { width, height } => write!(f, "invalid level dimensions {}x{}", width, height),
+            Self::InvalidMpxHeader => write!(f, "not a Megaplex MPX file"),
+            Self::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            Self::InvalidDemo(offset) => write!(f, "invalid demo data at byte {}", offset),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl From<std::io::Error> for LevelError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// An entry of the special port table. Special ports are one-way ports that change the
+/// gravity and freeze zonks and enemies when Murphy passes through them.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SpecialPort {
+    /// Index of the port in the play area.
+    pub position: usize,
+    pub gravity: bool,
+    pub freeze_zonks: bool,
+    pub freeze_enemies: bool,
+}
+
+impl SpecialPort {
+    pub fn new(position: usize) -> Self {
+        Self { position, ..Default::default() }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        // The position is stored big endian, as an offset in the game's word sized tile array.
+        let offset = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+        Self {
+            position: offset / 2,
+            gravity: bytes[2] == 1,
+            freeze_zonks: bytes[3] == 2,
+            freeze_enemies: bytes[4] == 1,
+        }
+    }
+
+    fn write_bytes(&self, bytes: &mut [u8]) {
+        bytes[..2].copy_from_slice(&((self.position * 2) as u16).to_be_bytes());
+        bytes[2] = self.gravity.into();
+        bytes[3] = if self.freeze_zonks { 2 } else { 0 };
+        bytes[4] = self.freeze_enemies.into();
+    }
+}
+
+/// The metadata of a level. The raw bytes are kept as is so that the level can be written
+/// back byte for byte, including the parts the editor doesn't know about.
+#[derive(Clone, PartialEq)]
+pub struct LevelInfo {
+    bytes: [u8; INFO_SIZE],
+}
+
+impl LevelInfo {
+    pub fn new() -> Self {
+        let mut res = Self { bytes: [0u8; INFO_SIZE] };
+        res.set_title("NEW LEVEL").unwrap();
+        res
+    }
+
+    pub fn from_bytes(bytes: [u8; INFO_SIZE]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn bytes(&self) -> &[u8; INFO_SIZE] {
+        &self.bytes
+    }
+
+    /// The title of the level, including the padding.
+    pub fn title(&self) -> String {
+        self.bytes[TITLE_OFFSET..TITLE_OFFSET + TITLE_LENGTH].iter().map(|&b| b as char).collect()
+    }
+
+    /// Sets the title, padding it with spaces to `TITLE_LENGTH` characters. Fails if the title
+    /// is too long or contains characters the game can't display.
+    pub fn set_title(&mut self, title: &str) -> Result<(), LevelError> {
+        if !is_valid_title(title) {
+            return Err(LevelError::InvalidTitle(title.to_owned()));
+        }
+        let field = &mut self.bytes[TITLE_OFFSET..TITLE_OFFSET + TITLE_LENGTH];
+        field.fill(b' ');
+        field[..title.len()].copy_from_slice(title.as_bytes());
+        Ok(())
+    }
+
+    pub fn gravity(&self) -> bool {
+        self.bytes[GRAVITY_OFFSET] != 0
+    }
+
+    pub fn set_gravity(&mut self, gravity: bool) {
+        self.bytes[GRAVITY_OFFSET] = gravity.into();
+    }
+
+    pub fn freeze_zonks(&self) -> bool {
+        self.bytes[FREEZE_ZONKS_OFFSET] == 2
+    }
+
+    pub fn set_freeze_zonks(&mut self, freeze: bool) {
+        self.bytes[FREEZE_ZONKS_OFFSET] = if freeze { 2 } else { 0 };
+    }
+
+    /// Number of infotrons needed to open the exit. Zero means all the infotrons in the level.
+    pub fn infotrons_needed(&self) -> u8 {
+        self.bytes[INFOTRONS_NEEDED_OFFSET]
+    }
+
+    pub fn set_infotrons_needed(&mut self, count: u8) {
+        self.bytes[INFOTRONS_NEEDED_OFFSET] = count;
+    }
+
+    pub fn special_ports(&self) -> Vec<SpecialPort> {
+        let count = (self.bytes[SPECIAL_PORT_COUNT_OFFSET] as usize).min(MAX_SPECIAL_PORTS);
+        self.bytes[SPECIAL_PORTS_OFFSET..]
+            .chunks_exact(SPECIAL_PORT_SIZE)
+            .take(count)
+            .map(SpecialPort::from_bytes)
+            .collect()
+    }
+
+    pub fn set_special_ports(&mut self, ports: &[SpecialPort]) -> Result<(), LevelError> {
+        if ports.len() > MAX_SPECIAL_PORTS {
+            return Err(LevelError::TooManySpecialPorts);
+        }
+        self.bytes[SPECIAL_PORT_COUNT_OFFSET] = ports.len() as u8;
+        let table = &mut self.bytes[SPECIAL_PORTS_OFFSET..SPECIAL_PORTS_OFFSET + MAX_SPECIAL_PORTS * SPECIAL_PORT_SIZE];
+        for (i, entry) in table.chunks_exact_mut(SPECIAL_PORT_SIZE).enumerate() {
+            match ports.get(i) {
+                Some(port) => port.write_bytes(entry),
+                None => entry.fill(0),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn version(&self) -> u8 {
+        self.bytes[VERSION_OFFSET]
+    }
+
+    pub fn set_version(&mut self, version: u8) {
+        self.bytes[VERSION_OFFSET] = version;
+    }
+}
+
+impl Default for LevelInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn is_valid_title(title: &str) -> bool {
+    title.len() <= TITLE_LENGTH && title.chars().all(|c| TITLE_CHARACTERS.contains(c))
+}
+
+/// The directions things move in the play area.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Right, Down, Left, Up
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::Right, Direction::Down, Direction::Left, Direction::Up];
+
+    /// The change of column and row when moving one tile in the direction.
+    pub fn offset(self) -> (isize, isize) {
+        match self {
+            Direction::Right => (1, 0),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Up => (0, -1),
+        }
+    }
+
+    /// The direction after turning 90 degrees counterclockwise.
+    pub fn turned_left(self) -> Self {
+        match self {
+            Direction::Right => Direction::Up,
+            Direction::Down => Direction::Right,
+            Direction::Left => Direction::Down,
+            Direction::Up => Direction::Left,
+        }
+    }
+
+    /// The direction after turning 90 degrees clockwise.
+    pub fn turned_right(self) -> Self {
+        self.turned_left().opposite()
+    }
+
+    pub fn opposite(self) -> Self {
+        self.turned_left().turned_left()
+    }
+}
+
+#[derive(Clone)]
+pub struct Level {
+    width: usize,
+    height: usize,
+    /// The tiles, row by row. Always `width * height` tiles.
+    pub play_area: Vec<Tile>,
+    pub info: LevelInfo,
+    /// Data following the level in .SP files, usually a demo. Not stored in LEVELS.DAT.
+    pub demo: Vec<u8>,
+}
+
+impl Level {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            play_area: vec![Tile::Empty; width * height],
+            info: LevelInfo::new(),
+            demo: vec![],
+        }
+    }
+
+    /// Parses a level from exactly `width * height + INFO_SIZE` bytes.
+    pub fn from_bytes(width: usize, height: usize, bytes: &[u8]) -> Result<Self, LevelError> {
+        let size = width * height;
+        if bytes.len() != size + INFO_SIZE {
+            return Err(LevelError::InvalidSize(bytes.len()));
+        }
+        let play_area = bytes[..size].iter().map(|&byte| Tile::from_byte(byte)).collect();
+        let mut info = [0u8; INFO_SIZE];
+        info.copy_from_slice(&bytes[size..]);
+        Ok(Self { width, height, play_area, info: LevelInfo::from_bytes(info), demo: vec![] })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Indicates whether the level has the dimensions of the levels in the original game.
+    pub fn is_standard_size(&self) -> bool {
+        self.width == PLAY_AREA_WIDTH && self.height == PLAY_AREA_HEIGHT
+    }
+
+    /// Fails unless the level can be stored in LEVELS.DAT or a .SP file.
+    pub fn check_standard_size(&self) -> Result<(), LevelError> {
+        match self.is_standard_size() {
+            true => Ok(()),
+            false => Err(LevelError::InvalidDimensions { width: self.width, height: self.height }),
+        }
+    }
+
+    pub fn col_row(&self, index: usize) -> (usize, usize) {
+        (index % self.width, index / self.width)
+    }
+
+    pub fn index(&self, col: usize, row: usize) -> usize {
+        col + self.width * row
+    }
+
+    /// The index of the tile `offset` columns and rows away from the one at `index`, if
+    /// it's inside the level.
+    pub fn offset_index(&self, index: usize, offset: (isize, isize)) -> Option<usize> {
+        let (col, row) = self.col_row(index);
+        let col = col.checked_add_signed(offset.0).filter(|&col| col < self.width)?;
+        let row = row.checked_add_signed(offset.1).filter(|&row| row < self.height)?;
+        Some(self.index(col, row))
+    }
+
+    /// The index of the neighbour of the tile at `index` in the direction, if it's in the level.
+    pub fn neighbour(&self, index: usize, direction: Direction) -> Option<usize> {
+        self.offset_index(index, direction.offset())
+    }
+
+    /// Places the tile at `index`. When that overwrites half of a two-tile RAM chip, the
+    /// other half is turned into a one-tile RAM chip so that no half chip is left behind.
+    pub fn set_tile(&mut self, index: usize, tile: Tile) {
+        let old = self.play_area[index];
+        if old == tile {
+            return;
+        }
+        if let Some((offset, partner)) = old.ram_chip_partner() {
+            if let Some(partner_index) = self.offset_index(index, offset).filter(|&i| self.play_area[i] == partner) {
+                self.play_area[partner_index] = Tile::RamChip;
+            }
+        }
+        self.play_area[index] = tile;
+    }
+
+    /// The tiles connected to the one at `index` through tiles of the same kind, horizontally
+    /// and vertically, including the tile itself.
+    pub fn connected_tiles(&self, index: usize) -> Vec<usize> {
+        let tile = self.play_area[index];
+        let mut found = vec![false; self.play_area.len()];
+        found[index] = true;
+        let mut stack = vec![index];
+        let mut connected = vec![];
+        while let Some(i) = stack.pop() {
+            connected.push(i);
+            for offset in [(1, 0), (0, 1), (-1, 0), (0, -1)] {
+                if let Some(next) = self.offset_index(i, offset).filter(|&n| !found[n] && self.play_area[n] == tile) {
+                    found[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        connected
+    }
+
+    /// Number of infotrons placed in the play area.
+    pub fn infotron_count(&self) -> usize {
+        self.play_area.iter().filter(|&&tile| tile == Tile::Infotron).count()
+    }
+
+    /// The special port table entry of the port at `index`, if any.
+    pub fn special_port(&self, index: usize) -> Option<SpecialPort> {
+        self.info.special_ports().into_iter().find(|port| port.position == index)
+    }
+
+    /// Adds or updates the special port table entry of the port at `port.position`, turning
+    /// the port into a gravity port.
+    pub fn set_special_port(&mut self, port: SpecialPort) -> Result<(), LevelError> {
+        let gravity_port = self.play_area[port.position].to_gravity_port()
+            .ok_or(LevelError::NotAPort(port.position))?;
+        let mut ports = self.info.special_ports();
+        match ports.iter_mut().find(|p| p.position == port.position) {
+            Some(existing) => *existing = port,
+            None => ports.push(port),
+        }
+        self.info.set_special_ports(&ports)?;
+        self.play_area[port.position] = gravity_port;
+        Ok(())
+    }
+
+    /// Removes the special port table entry of the port at `index`, turning the port back
+    /// into a regular one.
+    pub fn remove_special_port(&mut self, index: usize) {
+        let mut ports = self.info.special_ports();
+        ports.retain(|port| port.position != index);
+        self.info.set_special_ports(&ports).unwrap();
+        if let Some(port) = self.play_area[index].to_regular_port() {
+            self.play_area[index] = port;
+        }
+    }
+
+    /// Drops the special port table entries whose gravity port has been overwritten.
+    pub fn remove_stale_special_ports(&mut self) {
+        let ports = self.info.special_ports();
+        let valid: Vec<SpecialPort> = ports.iter()
+            .filter(|port| self.play_area.get(port.position).is_some_and(|tile| tile.is_gravity_port()))
+            .copied()
+            .collect();
+        if valid.len() != ports.len() {
+            self.info.set_special_ports(&valid).unwrap();
+        }
+    }
+
+    /// Appends the tiles and the info block of this level to `out`. The demo isn't included.
+    pub fn write_bytes(&self, out: &mut Vec<u8>) {
+        out.extend(self.play_area.iter().map(|&tile| u8::from(tile)));
+        out.extend_from_slice(self.info.bytes());
+    }
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self::new(PLAY_AREA_WIDTH, PLAY_AREA_HEIGHT)
+    }
+}
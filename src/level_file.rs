@@ -0,0 +1,43 @@
+// Reading and writing of levels in any of the supported formats, chosen by the extension
+// of the file.
+
+use std::path::Path;
+
+use crate::level::{Level, LevelError};
+use crate::{levels_dat, mpx, sp_file, text_file};
+
+/// Reads all the levels in the file. A .SP file contains just one level.
+pub fn read(path: &Path) -> Result<Vec<Level>, LevelError> {
+    if sp_file::is_sp_file(path) {
+        sp_file::read(path).map(|level| vec![level])
+    }
+    else if mpx::is_mpx_file(path) {
+        mpx::read(path)
+    }
+    else if text_file::is_text_file(path) {
+        text_file::read(path)
+    }
+    else {
+        levels_dat::read(path)
+    }
+}
+
+/// Writes the levels to the file. A .SP file can only be written with exactly one level,
+/// and LEVELS.DAT with exactly `levels_dat::LEVEL_COUNT` levels.
+pub fn write(path: &Path, levels: &[Level]) -> Result<(), LevelError> {
+    if sp_file::is_sp_file(path) {
+        match levels {
+            [level] => sp_file::write(path, level),
+            _ => Err(LevelError::WrongLevelCount { expected: 1, found: levels.len() }),
+        }
+    }
+    else if mpx::is_mpx_file(path) {
+        mpx::write(path, levels)
+    }
+    else if text_file::is_text_file(path) {
+        text_file::write(path, levels)
+    }
+    else {
+        levels_dat::write(path, levels)
+    }
+}
@@ -0,0 +1,58 @@
+// Reading and writing of LEVELS.DAT, which is simply all the levels of the game stored
+// back to back.
+
+use std::path::Path;
+
+use crate::level::{Level, LevelError, LEVEL_SIZE, PLAY_AREA_HEIGHT, PLAY_AREA_WIDTH};
+
+/// Number of levels in the original LEVELS.DAT.
+pub const LEVEL_COUNT: usize = 111;
+
+pub fn from_bytes(bytes: &[u8]) -> Result<Vec<Level>, LevelError> {
+    if bytes.len() != LEVEL_COUNT * LEVEL_SIZE {
+        return Err(LevelError::InvalidSize(bytes.len()));
+    }
+    bytes.chunks_exact(LEVEL_SIZE)
+        .map(|chunk| Level::from_bytes(PLAY_AREA_WIDTH, PLAY_AREA_HEIGHT, chunk))
+        .collect()
+}
+
+pub fn to_bytes(levels: &[Level]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(levels.len() * LEVEL_SIZE);
+    for level in levels {
+        level.write_bytes(&mut bytes);
+    }
+    bytes
+}
+
+pub fn read(path: &Path) -> Result<Vec<Level>, LevelError> {
+    from_bytes(&std::fs::read(path)?)
+}
+
+pub fn write(path: &Path, levels: &[Level]) -> Result<(), LevelError> {
+    if levels.len() != LEVEL_COUNT {
+        return Err(LevelError::WrongLevelCount { expected: LEVEL_COUNT, found: levels.len() });
+    }
+    for level in levels {
+        level.check_standard_size()?;
+    }
+    Ok(std::fs::write(path, to_bytes(levels))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let bytes: Vec<u8> = (0..LEVEL_COUNT * LEVEL_SIZE).map(|i| (i * 7 % 251) as u8).collect();
+        let levels = from_bytes(&bytes).unwrap();
+        assert_eq!(levels.len(), LEVEL_COUNT);
+        assert_eq!(to_bytes(&levels), bytes);
+    }
+
+    #[test]
+    fn wrong_size() {
+        assert!(matches!(from_bytes(&[0; LEVEL_SIZE]), Err(LevelError::InvalidSize(LEVEL_SIZE))));
+    }
+}
@@ -0,0 +1,16 @@
+// The level model and the file formats, shared by the editor and the command line tool.
+
+pub mod tile;
+pub mod block;
+pub mod level;
+pub mod levels_dat;
+pub mod sp_file;
+pub mod mpx;
+pub mod text_file;
+pub mod level_file;
+pub mod reachability;
+pub mod render;
+pub mod validate;
+pub mod simulation;
+pub mod demo;
+pub mod solver;
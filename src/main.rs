@@ -1,9 +1,12 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
 use eframe::egui;
 
 mod tool_panel;
-use tool_panel::ToolPanel;
+use tool_panel::{OperatingMode, ToolPanel};
 
 mod status_panel;
 use status_panel::StatusPanel;
@@ -12,13 +15,21 @@ mod editor_panel;
 use editor_panel::EditorPanel;
 
 mod top_panel;
-use top_panel::TopPanel;
+use top_panel::{MenuCommand, TopPanel};
 
 mod images;
+use images::Images;
+
+mod history;
+
+use supaleve_rs::{block, demo, level, level_file, levels_dat, mpx, reachability, simulation, solver, sp_file, text_file, tile, validate};
+use level::Level;
 
 fn main() {
-    let mut options = eframe::NativeOptions::default();
-    options.fullscreen = false;
+    let options = eframe::NativeOptions {
+        fullscreen: false,
+        ..Default::default()
+    };
 
     eframe::run_native(
         "Supaplex Level Editor",
@@ -28,20 +39,211 @@ fn main() {
 }
 
 
+/// What to do with the unsaved changes before running a menu command.
+enum UnsavedChanges {
+    Save, Discard, Cancel
+}
+
 struct SupaleveApp {
     tool_panel: ToolPanel,
     status_panel: StatusPanel,
     editor_panel: EditorPanel,
     top_panel: TopPanel,
+    error_message: Option<String>,
+    path: Option<PathBuf>, // where the levels were loaded from or last saved to
+    pending_command: Option<MenuCommand>, // waiting for the user to decide about unsaved changes
+    allow_close: bool,
+    window_title: String,
 }
 
 impl Default for SupaleveApp {
     fn default() -> Self {
+        let images = Rc::new(Images::new());
         Self {
-            tool_panel: ToolPanel::new("Tools", 120.0),
-            status_panel: StatusPanel::new(120.0),
-            editor_panel: EditorPanel::new("Supaplex level editor"),
+            tool_panel: ToolPanel::new("Tools", 120.0, images.clone()),
+            status_panel: StatusPanel::new(160.0),
+            editor_panel: EditorPanel::new("Supaplex level editor", images),
             top_panel: TopPanel::new(),
+            error_message: None,
+            path: None,
+            pending_command: None,
+            allow_close: false,
+            window_title: String::new(),
+        }
+    }
+}
+
+impl SupaleveApp {
+    fn open(&mut self, path: &Path) {
+        match level_file::read(path) {
+            Ok(levels) if levels.is_empty() => self.error_message = Some(format!("Failed to open {}: there are no levels in it", path.display())),
+            Ok(levels) => {
+                self.editor_panel.set_levels(levels);
+                self.path = Some(path.to_owned());
+            }
+            Err(e) => self.error_message = Some(format!("Failed to open {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Writes all the levels to `path`, in the format given by the extension, and saves
+    /// further changes there. Returns false if that failed. Warns when demos were left out
+    /// because LEVELS.DAT can't hold them, without holding up the pending command.
+    fn write_levels(&mut self, path: &Path) -> bool {
+        match level_file::write(path, self.editor_panel.levels()) {
+            Ok(()) => {
+                self.path = Some(path.to_owned());
+                let demos_saved = sp_file::is_sp_file(path) || mpx::is_mpx_file(path) || text_file::is_text_file(path);
+                self.editor_panel.mark_saved();
+                let with_demos: Vec<String> = self.editor_panel.levels().iter().enumerate()
+                    .filter(|(_, level)| !level.demo.is_empty())
+                    .map(|(index, _)| (index + 1).to_string())
+                    .collect();
+                if !demos_saved && !with_demos.is_empty() {
+                    self.error_message = Some(format!("Saved {}, but LEVELS.DAT can't hold demos, so the demos of levels {} \
+                        weren't saved. Export those levels as .SP files to keep them, or remove them to stop this warning.",
+                        path.display(), with_demos.join(", ")));
+                }
+                true
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Failed to save {}: {}", path.display(), e));
+                false
+            }
+        }
+    }
+
+    fn save_as(&mut self, path: &Path) {
+        if sp_file::is_sp_file(path) && self.editor_panel.levels().len() != 1 {
+            self.export_level(path);
+        }
+        else if self.write_levels(path) {
+            if let Some(command) = self.pending_command.take() {
+                self.run_command(command);
+            }
+        }
+    }
+
+    /// Saves the levels to the file they were loaded from. Asks for the file if there's
+    /// none yet, in which case false is returned.
+    fn save(&mut self) -> bool {
+        match self.path.clone() {
+            Some(path) => self.write_levels(&path),
+            None => {
+                self.top_panel.show_save_as_dialog();
+                false
+            }
+        }
+    }
+
+    /// Replaces the selected level with the one in a file of any format containing exactly
+    /// one level, usually a .SP file.
+    fn import_level(&mut self, path: &Path) {
+        match level_file::read(path) {
+            Ok(mut levels) if levels.len() == 1 => self.editor_panel.set_level(levels.remove(0)),
+            Ok(levels) => self.error_message = Some(format!("Failed to import {}: expected one level, found {}", path.display(), levels.len())),
+            Err(e) => self.error_message = Some(format!("Failed to import {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Saves the selected level as a .SP file.
+    fn export_level(&mut self, path: &Path) {
+        if let Err(e) = sp_file::write(path, self.editor_panel.level()) {
+            self.error_message = Some(format!("Failed to export {}: {}", path.display(), e));
+        }
+    }
+
+    fn on_command(&mut self, command: MenuCommand) {
+        match command {
+            MenuCommand::Save | MenuCommand::Transform(_) | MenuCommand::ZoomToFit | MenuCommand::ActualSize
+                => self.run_command(command),
+            _ if self.editor_panel.is_modified() => self.pending_command = Some(command),
+            _ => self.run_command(command),
+        }
+    }
+
+    fn run_command(&mut self, command: MenuCommand) {
+        match command {
+            MenuCommand::New => {
+                self.editor_panel.set_levels(vec![Level::default(); levels_dat::LEVEL_COUNT]);
+                self.path = None;
+            }
+            MenuCommand::Open => self.top_panel.show_open_dialog(),
+            MenuCommand::Save => { self.save(); }
+            MenuCommand::Exit => self.allow_close = true,
+            MenuCommand::Transform(transform) => {
+                // Only these formats can hold levels of any size.
+                let any_size = self.path.as_deref().is_some_and(|path| mpx::is_mpx_file(path) || text_file::is_text_file(path));
+                if let Err(message) = self.editor_panel.transform(transform, any_size) {
+                    self.error_message = Some(message);
+                }
+            }
+            MenuCommand::ZoomToFit => self.editor_panel.zoom_to_fit(),
+            MenuCommand::ActualSize => self.editor_panel.set_zoom(1.),
+        }
+    }
+
+    fn file_name(&self) -> String {
+        match &self.path {
+            Some(path) => path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            None => String::from("Untitled"),
+        }
+    }
+
+    /// Asks whether to save the changes before running the pending command.
+    fn show_unsaved_changes(&mut self, ctx: &egui::Context) {
+        if self.pending_command.is_none() || self.top_panel.dialog_open() {
+            return;
+        }
+        let mut choice = None;
+        egui::Window::new("Unsaved changes")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("Save changes to {}?", self.file_name()));
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() { choice = Some(UnsavedChanges::Save); }
+                    if ui.button("Discard").clicked() { choice = Some(UnsavedChanges::Discard); }
+                    if ui.button("Cancel").clicked() { choice = Some(UnsavedChanges::Cancel); }
+                });
+            });
+        let proceed = match choice {
+            Some(UnsavedChanges::Save) => self.save(),
+            Some(UnsavedChanges::Discard) => true,
+            Some(UnsavedChanges::Cancel) => {
+                self.pending_command = None;
+                false
+            }
+            None => false,
+        };
+        if proceed {
+            if let Some(command) = self.pending_command.take() {
+                self.run_command(command);
+            }
+        }
+    }
+
+    fn update_window_title(&mut self, frame: &mut eframe::Frame) {
+        let modified = if self.editor_panel.is_modified() { "*" } else { "" };
+        let title = format!("Supaplex Level Editor - {}{}", self.file_name(), modified);
+        if title != self.window_title {
+            frame.set_window_title(&title);
+            self.window_title = title;
+        }
+    }
+
+    fn show_error(&mut self, ctx: &egui::Context) {
+        if let Some(message) = &self.error_message {
+            let mut close = false;
+            egui::Window::new("Error")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(message);
+                    close = ui.button("OK").clicked();
+                });
+            if close {
+                self.error_message = None;
+            }
         }
     }
 }
@@ -50,22 +252,51 @@ impl eframe::App for SupaleveApp {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         
         self.top_panel.update(ctx);
-        if self.top_panel.open_selected() {
-            if self.top_panel.path_selected() {
-                //todo!("Open: path: {:?}", self.top_panel.path());
-                self.top_panel.close_dialog();
-            }
+        if let Some(command) = self.top_panel.take_command() {
+            self.on_command(command);
         }
-        else if self.top_panel.save_as_selected() {
+
+        let dialog_action: Option<fn(&mut Self, &Path)> =
+            if self.top_panel.open_selected() { Some(Self::open) }
+            else if self.top_panel.save_as_selected() { Some(Self::save_as) }
+            else if self.top_panel.import_selected() { Some(Self::import_level) }
+            else if self.top_panel.export_selected() { Some(Self::export_level) }
+            else { None };
+
+        if let Some(action) = dialog_action {
             if self.top_panel.path_selected() {
-                //todo!("Save as: path: {:?}", self.top_panel.path());
+                if let Some(path) = self.top_panel.path() {
+                    action(self, &path);
+                }
                 self.top_panel.close_dialog();
             }
         }
         else {
             self.tool_panel.update(ctx, frame);
-            self.status_panel.update(ctx, frame);
-            self.editor_panel.update(ctx, frame, &self.tool_panel);
+            let selected_tile_index = self.editor_panel.selected_tile_index();
+            let status = self.status_panel.update(ctx, frame, self.editor_panel.level(), selected_tile_index);
+            if let Some(edited_level) = status.edited_level {
+                self.editor_panel.edit_level(|level| *level = edited_level);
+            }
+            if let Some(index) = status.clicked_tile {
+                self.tool_panel.set_operating_mode(OperatingMode::Select);
+                self.editor_panel.select_tile(index);
+            }
+            self.editor_panel.update(ctx, frame, &mut self.tool_panel);
+        }
+        self.show_unsaved_changes(ctx);
+        self.show_error(ctx);
+        self.update_window_title(frame);
+        if self.allow_close {
+            frame.close();
+        }
+    }
+
+    fn on_close_event(&mut self) -> bool {
+        if self.allow_close || !self.editor_panel.is_modified() {
+            return true;
         }
+        self.pending_command = Some(MenuCommand::Exit);
+        false
     }
 }
@@ -0,0 +1,138 @@
+// Reading and writing of Megaplex .MPX files, which contain any number of levels of any
+// size. The file starts with a header and a directory of the levels:
+//
+//   "MPX "              magic
+//   u16                 version, always 1
+//   u16                 number of levels
+//   per level:
+//     u16               width
+//     u16               height
+//     u32               offset of the level data, counting from 1
+//     u32               size of the level data
+//
+// All numbers are little endian. The level data is the tiles followed by the info block,
+// just like in .SP files, optionally followed by a demo.
+
+use std::path::Path;
+
+use crate::level::{Level, LevelError, INFO_SIZE};
+
+const MAGIC: &[u8; 4] = b"MPX ";
+const VERSION: u16 = 1;
+const HEADER_SIZE: usize = 8;
+const DIRECTORY_ENTRY_SIZE: usize = 12;
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<usize, LevelError> {
+    bytes.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+        .ok_or(LevelError::InvalidSize(bytes.len()))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<usize, LevelError> {
+    bytes.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+        .ok_or(LevelError::InvalidSize(bytes.len()))
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Result<Vec<Level>, LevelError> {
+    if bytes.len() < HEADER_SIZE || &bytes[..4] != MAGIC {
+        return Err(LevelError::InvalidMpxHeader);
+    }
+    let level_count = u16_at(bytes, 6)?;
+    (0..level_count).map(|i| {
+        let entry = HEADER_SIZE + i * DIRECTORY_ENTRY_SIZE;
+        let width = u16_at(bytes, entry)?;
+        let height = u16_at(bytes, entry + 2)?;
+        let offset = u32_at(bytes, entry + 4)?.saturating_sub(1);
+        let size = u32_at(bytes, entry + 8)?;
+        let level_size = width * height + INFO_SIZE;
+        if width == 0 || height == 0 {
+            return Err(LevelError::InvalidDimensions { width, height });
+        }
+        let data = bytes.get(offset..offset + size)
+            .filter(|data| data.len() >= level_size)
+            .ok_or(LevelError::InvalidSize(bytes.len()))?;
+        let mut level = Level::from_bytes(width, height, &data[..level_size])?;
+        level.demo = data[level_size..].to_vec();
+        Ok(level)
+    }).collect()
+}
+
+pub fn to_bytes(levels: &[Level]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(levels.len() as u16).to_le_bytes());
+
+    let mut offset = HEADER_SIZE + levels.len() * DIRECTORY_ENTRY_SIZE;
+    for level in levels {
+        let size = level.width() * level.height() + INFO_SIZE + level.demo.len();
+        bytes.extend_from_slice(&(level.width() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(level.height() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(offset as u32 + 1).to_le_bytes());
+        bytes.extend_from_slice(&(size as u32).to_le_bytes());
+        offset += size;
+    }
+    for level in levels {
+        level.write_bytes(&mut bytes);
+        bytes.extend_from_slice(&level.demo);
+    }
+    bytes
+}
+
+pub fn read(path: &Path) -> Result<Vec<Level>, LevelError> {
+    from_bytes(&std::fs::read(path)?)
+}
+
+pub fn write(path: &Path, levels: &[Level]) -> Result<(), LevelError> {
+    if levels.len() > u16::MAX as usize {
+        return Err(LevelError::InvalidSize(levels.len()));
+    }
+    if let Some(level) = levels.iter().find(|level| level.width() > u16::MAX as usize || level.height() > u16::MAX as usize) {
+        return Err(LevelError::InvalidDimensions { width: level.width(), height: level.height() });
+    }
+    Ok(std::fs::write(path, to_bytes(levels))?)
+}
+
+/// Indicates whether the path has the .MPX extension.
+pub fn is_mpx_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mpx"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A level of the size with all kinds of tile and info bytes.
+    fn level(width: usize, height: usize, seed: usize) -> Level {
+        let bytes: Vec<u8> = (0..width * height + INFO_SIZE).map(|i| ((i + seed) * 7 % 256) as u8).collect();
+        Level::from_bytes(width, height, &bytes).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut levels = vec![level(60, 24, 0), level(3, 100, 1), level(1, 1, 2)];
+        levels[1].demo = vec![2, 0x71, 0x72, 0xff];
+        let bytes = to_bytes(&levels);
+        let read = from_bytes(&bytes).unwrap();
+        assert_eq!(read.len(), levels.len());
+        for (read, level) in read.iter().zip(&levels) {
+            assert_eq!((read.width(), read.height()), (level.width(), level.height()));
+            assert_eq!(read.demo, level.demo);
+        }
+        assert_eq!(to_bytes(&read), bytes);
+    }
+
+    #[test]
+    fn too_wide() {
+        let path = std::env::temp_dir().join("supaleve-too-wide.mpx");
+        let result = write(&path, &[Level::new(70000, 1)]);
+        assert!(matches!(result, Err(LevelError::InvalidDimensions { width: 70000, height: 1 })));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn not_mpx() {
+        assert!(matches!(from_bytes(b"LEVELS.DAT"), Err(LevelError::InvalidMpxHeader)));
+    }
+}
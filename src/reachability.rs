@@ -0,0 +1,142 @@
+// Finds the parts of a level that Murphy can get to from the start, to spot infotrons,
+// terminals and exits that have been sealed off by mistake.
+//
+// Only the tiles Murphy can walk over, eat or pass through ports are followed. Objects
+// that can be moved or blown up, like zonks, disks and enemies, are treated as walls, so
+// a tile that is reported unreachable may still be reachable by pushing or blasting.
+
+use std::collections::VecDeque;
+
+use crate::level::{Direction, Level};
+use crate::tile::Tile;
+
+/// Indicates whether Murphy can move onto the tile, possibly eating it.
+fn is_passable(tile: Tile) -> bool {
+    matches!(tile, Tile::Empty | Tile::Base | Tile::Bug | Tile::Murphy | Tile::Infotron | Tile::FloppyRed)
+}
+
+/// Indicates whether Murphy can pass through the port when moving in the direction.
+pub fn port_allows(tile: Tile, direction: Direction) -> bool {
+    use Direction::*;
+    match tile {
+        Tile::PortRight | Tile::GravityPortRight => direction == Right,
+        Tile::PortDown | Tile::GravityPortDown => direction == Down,
+        Tile::PortLeft | Tile::GravityPortLeft => direction == Left,
+        Tile::PortUp | Tile::GravityPortUp => direction == Up,
+        Tile::Port2WayVertical => matches!(direction, Up | Down),
+        Tile::Port2WayHorizontal => matches!(direction, Left | Right),
+        Tile::Port4Way => true,
+        _ => false,
+    }
+}
+
+/// Indicates whether the tile is something Murphy has to get to: an infotron, a terminal
+/// or an exit.
+pub fn is_target(tile: Tile) -> bool {
+    matches!(tile, Tile::Infotron | Tile::Terminal | Tile::Exit)
+}
+
+/// The tiles Murphy can get to from the first Murphy of the level, or None if there's no
+/// Murphy. Terminals and exits count as reached when Murphy can get next to them.
+pub fn reachable(level: &Level) -> Option<Vec<bool>> {
+    let start = level.play_area.iter().position(|&tile| tile == Tile::Murphy)?;
+    let mut reached = vec![false; level.play_area.len()];
+    let mut queue = VecDeque::from([start]);
+    reached[start] = true;
+    while let Some(index) = queue.pop_front() {
+        for direction in Direction::ALL {
+            let Some(next) = level.neighbour(index, direction) else { continue };
+            let tile = level.play_area[next];
+            let next = if port_allows(tile, direction) {
+                match level.neighbour(next, direction) {
+                    Some(beyond) if is_passable(level.play_area[beyond]) => beyond,
+                    _ => continue,
+                }
+            }
+            else if matches!(tile, Tile::Terminal | Tile::Exit) {
+                reached[next] = true;
+                continue;
+            }
+            else if is_passable(tile) {
+                next
+            }
+            else {
+                continue;
+            };
+            if !reached[next] {
+                reached[next] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+    Some(reached)
+}
+
+/// The infotrons, terminals and exits that Murphy can't get to. Empty if there's no Murphy.
+pub fn unreachable_targets(level: &Level) -> Vec<usize> {
+    match reachable(level) {
+        Some(reached) => (0..level.play_area.len())
+            .filter(|&i| is_target(level.play_area[i]) && !reached[i])
+            .collect(),
+        None => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_file::test_level;
+
+    /// The columns and rows of the unreachable targets.
+    fn unreachable(rows: &[&str]) -> Vec<(usize, usize)> {
+        let level = test_level("", rows);
+        unreachable_targets(&level).into_iter().map(|i| level.col_row(i)).collect()
+    }
+
+    #[test]
+    fn through_base_and_bugs() {
+        assert_eq!(unreachable(&[
+            "#######",
+            "#M:B:I#",
+            "#####E#",
+        ]), []);
+    }
+
+    #[test]
+    fn port_direction() {
+        assert_eq!(unreachable(&[
+            "#####",
+            "#M>I#",
+            "#####",
+        ]), []);
+        assert_eq!(unreachable(&[
+            "#####",
+            "#M<I#",
+            "#####",
+        ]), [(3, 1)]);
+    }
+
+    #[test]
+    fn sealed_off() {
+        assert_eq!(unreachable(&[
+            "#######",
+            "#M:#IT#",
+            "###E###",
+        ]), [(4, 1), (5, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn terminals_and_exits_next_to_murphy() {
+        assert_eq!(unreachable(&[
+            "#####",
+            "#TME#",
+            "#####",
+        ]), []);
+    }
+
+    #[test]
+    fn no_murphy() {
+        assert_eq!(reachable(&test_level("", &["I"])), None);
+        assert_eq!(unreachable(&["I"]), []);
+    }
+}
@@ -0,0 +1,67 @@
+// Rendering of levels to images, using the same tile images as the editor.
+
+use std::path::Path;
+
+use image::{imageops, ImageResult, Rgba, RgbaImage};
+
+use crate::level::Level;
+use crate::tile::{Tile, TILE_IMAGES, TILE_IMAGE_BYTES};
+
+/// Size of a tile in pixels.
+pub const TILE_SIZE: u32 = 32;
+
+/// Decodes the tile images built into the executable, the same ones the editor shows.
+pub fn embedded_tile_images() -> ImageResult<Vec<RgbaImage>> {
+    TILE_IMAGE_BYTES.iter()
+        .map(|bytes| Ok(image::load_from_memory(bytes)?.to_rgba8()))
+        .collect()
+}
+
+/// Loads the tile images from the directory, e.g. "img", instead of the built in ones. The
+/// format of the images is guessed from the contents, as not all of them are what their
+/// extension says.
+pub fn load_tile_images(dir: &Path) -> ImageResult<Vec<RgbaImage>> {
+    TILE_IMAGES.iter()
+        .map(|name| Ok(image::load_from_memory(&std::fs::read(dir.join(name))?)?.to_rgba8()))
+        .collect()
+}
+
+/// Image for the tiles not known by the editor, like the one shown in the editor.
+fn unknown_tile_image() -> RgbaImage {
+    let last = TILE_SIZE - 1;
+    RgbaImage::from_fn(TILE_SIZE, TILE_SIZE, |x, y| {
+        if x == 0 || y == 0 || x == last || y == last || x == y || x == last - y {
+            Rgba([0xff, 0x00, 0xff, 0xff])
+        }
+        else {
+            Rgba([0x60, 0x00, 0x60, 0xff])
+        }
+    })
+}
+
+/// Draws the play area of the level, one `TILE_SIZE` square per tile.
+pub fn render_level(level: &Level, tile_images: &[RgbaImage]) -> RgbaImage {
+    let unknown = unknown_tile_image();
+    let mut image = RgbaImage::new(level.width() as u32 * TILE_SIZE, level.height() as u32 * TILE_SIZE);
+    for (i, &tile) in level.play_area.iter().enumerate() {
+        let (col, row) = level.col_row(i);
+        let tile_image = match tile {
+            Tile::Unknown(_) => &unknown,
+            known => &tile_images[known.byte() as usize],
+        };
+        imageops::replace(&mut image, tile_image, col as i64 * TILE_SIZE as i64, row as i64 * TILE_SIZE as i64);
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_with_embedded_images() {
+        let tile_images = embedded_tile_images().unwrap();
+        let image = render_level(&Level::new(3, 2), &tile_images);
+        assert_eq!(image.dimensions(), (3 * TILE_SIZE, 2 * TILE_SIZE));
+    }
+}
@@ -0,0 +1,580 @@
+// Steps the rules of the game over a copy of a level, to preview how it behaves without
+// exporting it and starting the game: zonks, infotrons and orange disks falling, zonks and
+// infotrons rolling off rounded things, snik snaks and electrons following walls, bugs
+// sparking and things blowing up. Murphy does what the player tells him to, so the level
+// can also be played.
+//
+// The game moves things a few pixels per frame, here everything moves a whole tile per
+// step. Like in the game, the tiles are updated row by row from the top left, and the
+// order matters when things compete for the same empty tile.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::level::{Direction, Level};
+use crate::reachability::port_allows;
+use crate::tile::Tile;
+
+/// Number of steps an explosion lasts before the tiles are cleared.
+const EXPLOSION_STEPS: u8 = 2;
+
+/// Number of steps a bug sparks for.
+const BUG_ACTIVE_STEPS: u8 = 3;
+
+/// Number of steps from dropping a red disk to it blowing up.
+const RED_DISK_STEPS: u8 = 8;
+
+/// What the player tells Murphy to do in a step.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    None,
+    Move(Direction),
+    /// Eat or collect the neighbouring tile without moving, with Space held down.
+    Snap(Direction),
+    /// Drop a red disk, which blows up after a while, with Space held down.
+    DropDisk,
+}
+
+impl Action {
+    /// A character for writing down a sequence of actions: R, D, L or U for moving, the
+    /// same in lower case for snapping, * for dropping a red disk and . for waiting.
+    pub fn symbol(self) -> char {
+        let direction = |direction| match direction {
+            Direction::Right => 'R',
+            Direction::Down => 'D',
+            Direction::Left => 'L',
+            Direction::Up => 'U',
+        };
+        match self {
+            Action::None => '.',
+            Action::Move(d) => direction(d),
+            Action::Snap(d) => direction(d).to_ascii_lowercase(),
+            Action::DropDisk => '*',
+        }
+    }
+}
+
+/// What a tile of the simulated level is doing, besides being the tile it is.
+#[derive(Copy, Clone, Debug, PartialEq, Hash)]
+enum State {
+    Idle,
+    /// A zonk, infotron or orange disk that moved down in the previous step.
+    Falling,
+    /// A snik snak or an electron heading in the direction.
+    Heading(Direction),
+    /// A bug that starts or stops sparking when `steps` runs out.
+    Bug { active: bool, steps: u8 },
+    /// A tile being blown up, which becomes `leaves` when `steps` runs out.
+    Exploding { steps: u8, leaves: Tile },
+}
+
+/// Whether things on top of the tile roll off it.
+pub(crate) fn is_rounded(tile: Tile) -> bool {
+    matches!(tile, Tile::Zonk | Tile::Infotron | Tile::RamChip
+        | Tile::RamLeft | Tile::RamRight | Tile::RamTop | Tile::RamBottom)
+}
+
+/// Whether the tile blows up when an explosion reaches it.
+fn is_explosive(tile: Tile) -> bool {
+    matches!(tile, Tile::Murphy | Tile::SnikSnak | Tile::Electron
+        | Tile::FloppyOrange | Tile::FloppyYellow | Tile::FloppyRed)
+}
+
+/// A level being played out by the rules of the game.
+#[derive(Clone)]
+pub struct Simulation {
+    level: Level,
+    states: Vec<State>,
+    moved: Vec<bool>, // the tiles already updated in the current step
+    step_count: u32,
+    random_seed: u16,
+    gravity: bool,
+    freeze_zonks: bool,
+    freeze_enemies: bool,
+    infotrons_needed: usize,
+    infotrons_collected: usize,
+    red_disks: usize,
+    ticking_disk: Option<(usize, u8)>, // the position of the dropped red disk and the steps left
+    won: bool,
+}
+
+impl Simulation {
+    /// Starts the simulation of a copy of the level. The level itself isn't changed.
+    pub fn new(level: &Level) -> Self {
+        let mut simulation = Self {
+            level: level.clone(),
+            states: vec![State::Idle; level.play_area.len()],
+            moved: vec![false; level.play_area.len()],
+            step_count: 0,
+            random_seed: 0,
+            gravity: level.info.gravity(),
+            freeze_zonks: level.info.freeze_zonks(),
+            freeze_enemies: false,
+            infotrons_needed: match level.info.infotrons_needed() {
+                0 => level.infotron_count(),
+                needed => needed as usize,
+            },
+            infotrons_collected: 0,
+            red_disks: 0,
+            ticking_disk: None,
+            won: false,
+        };
+        for index in 0..level.play_area.len() {
+            simulation.states[index] = match level.play_area[index] {
+                Tile::SnikSnak | Tile::Electron => State::Heading(Direction::Up),
+                Tile::Bug => State::Bug { active: false, steps: simulation.random_steps() },
+                _ => State::Idle,
+            };
+        }
+        simulation
+    }
+
+    /// The level as it is after the steps so far.
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn step_count(&self) -> u32 {
+        self.step_count
+    }
+
+    /// Number of infotrons Murphy has to collect before the exit opens.
+    pub fn infotrons_needed(&self) -> usize {
+        self.infotrons_needed
+    }
+
+    pub fn infotrons_collected(&self) -> usize {
+        self.infotrons_collected
+    }
+
+    /// Number of red disks Murphy has collected and not dropped yet.
+    pub fn red_disks(&self) -> usize {
+        self.red_disks
+    }
+
+    /// The position of Murphy, unless he's gone.
+    pub fn murphy_index(&self) -> Option<usize> {
+        self.level.play_area.iter().position(|&tile| tile == Tile::Murphy)
+    }
+
+    /// A hash of the situation the level is in, for telling apart the situations the solver
+    /// gets to. Base and bugs count as empty, so that eating a different trail through base
+    /// or a bug sparking at another time doesn't make a new situation. That leaves out the
+    /// odd solution where base still holds something up, but keeps the search from blowing
+    /// up on levels full of base.
+    pub(crate) fn state_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for (index, (&tile, &state)) in self.level.play_area.iter().zip(&self.states).enumerate() {
+            if !matches!((tile, state), (Tile::Empty | Tile::Base | Tile::Bug, State::Idle | State::Bug { .. })) {
+                (index, tile, state).hash(&mut hasher);
+            }
+        }
+        (self.infotrons_collected, self.red_disks, self.ticking_disk, self.won).hash(&mut hasher);
+        (self.gravity, self.freeze_zonks, self.freeze_enemies).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Roughly the number of bytes the simulation takes up.
+    pub(crate) fn memory_size(&self) -> usize {
+        let tile_size = std::mem::size_of::<Tile>() + std::mem::size_of::<State>() + std::mem::size_of::<bool>();
+        std::mem::size_of::<Self>() + self.level.play_area.len() * tile_size + self.level.demo.len()
+    }
+
+    /// Indicates whether Murphy has got to the exit with enough infotrons.
+    pub fn is_won(&self) -> bool {
+        self.won
+    }
+
+    /// Indicates whether Murphy has been blown up, or wasn't in the level to begin with.
+    pub fn is_murphy_dead(&self) -> bool {
+        !self.won && !self.level.play_area.contains(&Tile::Murphy)
+    }
+
+    /// Indicates whether the tile is being blown up. Exploding tiles are empty.
+    pub fn is_exploding(&self, index: usize) -> bool {
+        matches!(self.states[index], State::Exploding { .. })
+    }
+
+    /// Indicates whether the tile is a bug that is sparking, which kills Murphy if he
+    /// eats it.
+    pub fn is_bug_active(&self, index: usize) -> bool {
+        matches!(self.states[index], State::Bug { active: true, .. })
+    }
+
+    /// Advances the simulation by the time it takes for things to move one tile, with
+    /// Murphy doing what the action says.
+    pub fn step(&mut self, action: Action) {
+        self.step_count += 1;
+        self.moved.fill(false);
+        if let Some((position, steps)) = self.ticking_disk {
+            self.ticking_disk = (steps > 1).then_some((position, steps - 1));
+            if steps <= 1 && matches!(self.level.play_area[position], Tile::FloppyRed | Tile::Murphy) {
+                self.explode(position);
+            }
+        }
+        for index in 0..self.states.len() {
+            if let State::Exploding { steps, leaves } = self.states[index] {
+                if steps > 1 {
+                    self.states[index] = State::Exploding { steps: steps - 1, leaves };
+                }
+                else {
+                    self.level.play_area[index] = leaves;
+                    self.states[index] = State::Idle;
+                }
+                self.moved[index] = true;
+            }
+        }
+        for index in 0..self.states.len() {
+            if self.moved[index] {
+                continue;
+            }
+            match self.level.play_area[index] {
+                Tile::Zonk if !self.freeze_zonks => self.update_falling(index, true),
+                Tile::Infotron => self.update_falling(index, true),
+                Tile::FloppyOrange => self.update_falling(index, false),
+                Tile::SnikSnak | Tile::Electron if !self.freeze_enemies => self.update_enemy(index),
+                Tile::Murphy => self.update_murphy(index, action),
+                Tile::Bug => self.update_bug(index),
+                _ => {}
+            }
+        }
+    }
+
+    /// Indicates whether the tile is empty, so that things can move onto it.
+    fn is_free(&self, index: usize) -> bool {
+        self.level.play_area[index] == Tile::Empty && !self.is_exploding(index)
+    }
+
+    fn move_tile(&mut self, from: usize, to: usize, state: State) {
+        self.level.play_area[to] = self.level.play_area[from];
+        self.level.play_area[from] = Tile::Empty;
+        self.states[to] = state;
+        self.states[from] = State::Idle;
+        self.moved[to] = true;
+    }
+
+    /// Moves a zonk, an infotron or an orange disk down if there's room below it. Falling
+    /// onto Murphy, enemies or orange disks blows them up, and orange disks blow up when
+    /// they land. Zonks and infotrons that aren't falling roll off rounded things, to the
+    /// left if they can.
+    fn update_falling(&mut self, index: usize, rolls: bool) {
+        let falling = self.states[index] == State::Falling;
+        self.states[index] = State::Idle;
+        let Some(below) = self.level.neighbour(index, Direction::Down) else { return };
+        if self.is_free(below) {
+            self.move_tile(index, below, State::Falling);
+            return;
+        }
+        let below_tile = self.level.play_area[below];
+        if falling {
+            if self.level.play_area[index] == Tile::FloppyOrange {
+                self.explode(index);
+                return;
+            }
+            if matches!(below_tile, Tile::Murphy | Tile::SnikSnak | Tile::Electron | Tile::FloppyOrange) {
+                self.explode(below);
+                return;
+            }
+        }
+        if rolls && is_rounded(below_tile) {
+            for side in [Direction::Left, Direction::Right] {
+                let Some(beside) = self.level.neighbour(index, side).filter(|&i| self.is_free(i)) else { continue };
+                if self.level.neighbour(beside, Direction::Down).is_some_and(|i| self.is_free(i)) {
+                    self.move_tile(index, beside, State::Idle);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Moves a snik snak or an electron along the wall on its left: it turns left when it
+    /// can, goes straight on when it can't, and otherwise turns right on the spot. Running
+    /// into Murphy blows him up.
+    fn update_enemy(&mut self, index: usize) {
+        let heading = match self.states[index] {
+            State::Heading(direction) => direction,
+            _ => Direction::Up,
+        };
+        for direction in [heading.turned_left(), heading] {
+            let Some(next) = self.level.neighbour(index, direction) else { continue };
+            if self.level.play_area[next] == Tile::Murphy {
+                self.explode(next);
+                return;
+            }
+            if self.is_free(next) {
+                self.move_tile(index, next, State::Heading(direction));
+                return;
+            }
+        }
+        self.states[index] = State::Heading(heading.turned_right());
+    }
+
+    /// Carries out the action, unless Murphy falls because of gravity.
+    fn update_murphy(&mut self, index: usize, action: Action) {
+        if self.gravity && !matches!(action, Action::Snap(_)) {
+            if let Some(below) = self.level.neighbour(index, Direction::Down).filter(|&i| self.is_free(i)) {
+                self.move_murphy(index, below);
+                return;
+            }
+        }
+        match action {
+            Action::None => {}
+            Action::Move(direction) => self.update_murphy_move(index, direction),
+            Action::Snap(direction) => {
+                if let Some(next) = self.level.neighbour(index, direction) {
+                    self.take(next);
+                }
+            }
+            Action::DropDisk => {
+                if self.red_disks > 0 && self.ticking_disk.is_none() {
+                    self.red_disks -= 1;
+                    self.ticking_disk = Some((index, RED_DISK_STEPS));
+                }
+            }
+        }
+    }
+
+    /// Moves Murphy to the next tile in the direction, eating or collecting what's there,
+    /// pushing zonks and disks or passing through a port. Walking into the exit with enough
+    /// infotrons wins the level.
+    fn update_murphy_move(&mut self, index: usize, direction: Direction) {
+        let Some(next) = self.level.neighbour(index, direction) else { return };
+        if self.is_free(next) || self.take(next) {
+            self.move_murphy(index, next);
+            return;
+        }
+        let beyond = self.level.neighbour(next, direction).filter(|&i| self.is_free(i));
+        let horizontal = matches!(direction, Direction::Left | Direction::Right);
+        match self.level.play_area[next] {
+            Tile::Exit if self.infotrons_collected >= self.infotrons_needed => {
+                self.level.play_area[index] = Tile::Empty;
+                self.won = true;
+            }
+            Tile::Zonk | Tile::FloppyOrange if horizontal && self.states[next] != State::Falling => {
+                if let Some(beyond) = beyond {
+                    self.move_tile(next, beyond, State::Idle);
+                    self.move_murphy(index, next);
+                }
+            }
+            Tile::FloppyYellow => {
+                if let Some(beyond) = beyond {
+                    self.move_tile(next, beyond, State::Idle);
+                    self.move_murphy(index, next);
+                }
+            }
+            port if port_allows(port, direction) => {
+                if let Some(beyond) = beyond {
+                    self.move_murphy(index, beyond);
+                    if let Some(special) = self.level.special_port(next) {
+                        self.gravity = special.gravity;
+                        self.freeze_zonks = special.freeze_zonks;
+                        self.freeze_enemies = special.freeze_enemies;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves Murphy, leaving the red disk he dropped behind.
+    fn move_murphy(&mut self, from: usize, to: usize) {
+        self.move_tile(from, to, State::Idle);
+        if self.ticking_disk.is_some_and(|(position, _)| position == from) {
+            self.level.play_area[from] = Tile::FloppyRed;
+        }
+    }
+
+    /// Eats or collects the tile next to Murphy. Returns whether Murphy can move onto it.
+    /// Eating a sparking bug blows Murphy up, and using a terminal blows up the yellow
+    /// disks.
+    fn take(&mut self, index: usize) -> bool {
+        match self.level.play_area[index] {
+            Tile::Base => {}
+            Tile::Bug if self.is_bug_active(index) => {
+                self.explode(index);
+                return false;
+            }
+            Tile::Bug => {}
+            Tile::Infotron => self.infotrons_collected += 1,
+            Tile::FloppyRed if self.ticking_disk.is_none_or(|(position, _)| position != index) => self.red_disks += 1,
+            Tile::Terminal => {
+                for disk in 0..self.level.play_area.len() {
+                    if self.level.play_area[disk] == Tile::FloppyYellow {
+                        self.explode(disk);
+                    }
+                }
+                return false;
+            }
+            _ => return false,
+        }
+        self.level.play_area[index] = Tile::Empty;
+        self.states[index] = State::Idle;
+        true
+    }
+
+    /// Counts down to the bug starting or stopping to spark. The time between sparks is
+    /// random.
+    fn update_bug(&mut self, index: usize) {
+        self.states[index] = match self.states[index] {
+            State::Bug { active, steps } if steps > 0 => State::Bug { active, steps: steps - 1 },
+            State::Bug { active: true, .. } => State::Bug { active: false, steps: self.random_steps() },
+            _ => State::Bug { active: true, steps: BUG_ACTIVE_STEPS },
+        };
+    }
+
+    /// Blows up the tile and the ones around it, except hardware. Murphy, enemies and disks
+    /// caught in the explosion blow up in turn. Electrons leave infotrons behind.
+    fn explode(&mut self, center: usize) {
+        let mut centers = vec![center];
+        while let Some(center) = centers.pop() {
+            let leaves = if self.level.play_area[center] == Tile::Electron { Tile::Infotron } else { Tile::Empty };
+            for offset in [(-1, -1), (0, -1), (1, -1), (-1, 0), (0, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                let Some(index) = self.level.offset_index(center, offset) else { continue };
+                let tile = self.level.play_area[index];
+                if tile.is_hardware() || centers.contains(&index) || (index != center && self.is_exploding(index)) {
+                    continue;
+                }
+                if index != center && is_explosive(tile) {
+                    centers.push(index);
+                    continue;
+                }
+                self.level.play_area[index] = Tile::Empty;
+                self.states[index] = State::Exploding { steps: EXPLOSION_STEPS, leaves };
+                self.moved[index] = true;
+            }
+        }
+    }
+
+    /// A random number of steps for a bug to wait before sparking, from the random number
+    /// generator of the game.
+    fn random_steps(&mut self) -> u8 {
+        self.random_seed = self.random_seed.wrapping_mul(0x5e5).wrapping_add(0x31);
+        8 + (self.random_seed >> 1) as u8 % 32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_file::test_level;
+    use Direction::*;
+
+    /// The simulation of the level after the actions, one per step.
+    fn simulate(fields: &str, rows: &[&str], actions: &[Action]) -> Simulation {
+        let mut simulation = Simulation::new(&test_level(fields, rows));
+        for &action in actions {
+            simulation.step(action);
+        }
+        simulation
+    }
+
+    fn tile_at(simulation: &Simulation, col: usize, row: usize) -> Tile {
+        let level = simulation.level();
+        level.play_area[level.index(col, row)]
+    }
+
+    #[test]
+    fn zonk_falls_onto_murphy() {
+        let rows = ["#O#", "#.#", "#M#", "###"];
+        let simulation = simulate("", &rows, &[Action::None]);
+        assert_eq!(tile_at(&simulation, 1, 1), Tile::Zonk);
+        assert!(!simulation.is_murphy_dead());
+        let simulation = simulate("", &rows, &[Action::None; 2]);
+        assert!(simulation.is_murphy_dead());
+        assert!(simulation.is_exploding(simulation.level().index(1, 2)));
+    }
+
+    #[test]
+    fn rolls_off_rounded_tiles() {
+        let simulation = simulate("", &[
+            ".O.O",
+            ".O.#",
+            "####",
+        ], &[Action::None]);
+        assert_eq!(tile_at(&simulation, 0, 0), Tile::Zonk);
+        assert_eq!(tile_at(&simulation, 1, 0), Tile::Empty);
+        assert_eq!(tile_at(&simulation, 3, 0), Tile::Zonk); // on a wall, which isn't rounded
+    }
+
+    #[test]
+    fn exploding_electron_leaves_infotrons() {
+        let simulation = simulate("", &[
+            "#####",
+            "##O##",
+            "##.##",
+            "##e##",
+            "#####",
+        ], &[Action::None; 2]);
+        assert!(simulation.is_exploding(simulation.level().index(2, 3)));
+        let simulation = simulate("", &[
+            "#####",
+            "##O##",
+            "##.##",
+            "##e##",
+            "#####",
+        ], &[Action::None; 4]);
+        assert_eq!(tile_at(&simulation, 2, 2), Tile::Infotron);
+        assert_eq!(tile_at(&simulation, 2, 3), Tile::Infotron);
+    }
+
+    #[test]
+    fn orange_disk_explodes_on_landing() {
+        let simulation = simulate("", &[
+            "###",
+            "#o#",
+            "#.#",
+            "#:#",
+            "###",
+        ], &[Action::None; 2]);
+        let level = simulation.level();
+        assert!(simulation.is_exploding(level.index(1, 2)));
+        assert!(simulation.is_exploding(level.index(1, 3)));
+    }
+
+    #[test]
+    fn terminal_sets_off_pushed_yellow_disk() {
+        let rows = [
+            "#######",
+            "#My...#",
+            "#T#####",
+        ];
+        let simulation = simulate("", &rows, &[Action::Move(Right)]);
+        assert_eq!(tile_at(&simulation, 2, 1), Tile::Murphy);
+        assert_eq!(tile_at(&simulation, 3, 1), Tile::FloppyYellow);
+        let simulation = simulate("", &rows, &[Action::Move(Right), Action::Move(Left), Action::Move(Down)]);
+        assert!(simulation.is_exploding(simulation.level().index(3, 1)));
+        assert_eq!(tile_at(&simulation, 1, 1), Tile::Murphy);
+    }
+
+    #[test]
+    fn gravity_port_turns_on_gravity() {
+        let rows = [
+            "#####",
+            "#M}.#",
+            "###.#",
+            "#####",
+        ];
+        let simulation = simulate("special_port = 2 1 1 0 0", &rows, &[Action::Move(Right), Action::None]);
+        assert_eq!(tile_at(&simulation, 3, 2), Tile::Murphy);
+        let rows = rows.map(|row| row.replace('}', ">"));
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let simulation = simulate("", &rows, &[Action::Move(Right), Action::None]);
+        assert_eq!(tile_at(&simulation, 3, 1), Tile::Murphy);
+    }
+
+    #[test]
+    fn wins_at_exit_with_enough_infotrons() {
+        let rows = [
+            "######",
+            "#IME.#",
+            "######",
+        ];
+        let simulation = simulate("", &rows, &[Action::Move(Right)]);
+        assert!(!simulation.is_won());
+        assert_eq!(tile_at(&simulation, 2, 1), Tile::Murphy);
+        let simulation = simulate("", &rows, &[Action::Move(Left), Action::Move(Right), Action::Move(Right)]);
+        assert_eq!(simulation.infotrons_collected(), 1);
+        assert!(simulation.is_won());
+        assert!(!simulation.is_murphy_dead());
+    }
+}
@@ -0,0 +1,163 @@
+// Searches for a way to complete a level, to prove that it can be done. The search is
+// breadth first over the situations the level can get into, so the solution found is one
+// of the shortest. Situations are told apart by their hashes, which leave out the base
+// and the bugs, and which in rare cases may make the search miss a solution.
+//
+// Each situation keeps the whole simulated level, so the search runs out of memory long
+// before it runs out of situations on all but small or simple levels. The limits keep it
+// from taking over the machine.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::level::{Direction, Level};
+use crate::simulation::{Action, Simulation};
+
+/// How long and how much memory the search may take.
+#[derive(Copy, Clone, Debug)]
+pub struct Limits {
+    pub time: Duration,
+    /// Roughly the number of bytes.
+    pub memory: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self { time: Duration::from_secs(10), memory: 256 << 20 }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Solution {
+    /// What Murphy has to do in each step to get to the exit.
+    Solved(Vec<Action>),
+    /// Every situation the level can get into was tried.
+    Unsolvable,
+    /// The time or the memory ran out.
+    OutOfBounds,
+}
+
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Solution::Solved(actions) => {
+                let moves: String = actions.iter().map(|action| action.symbol()).collect();
+                write!(f, "solved in {} steps: {}", actions.len(), moves)
+            }
+            Solution::Unsolvable => write!(f, "unsolvable"),
+            Solution::OutOfBounds => write!(f, "unsolvable within bounds"),
+        }
+    }
+}
+
+/// A situation found by the search, and how it was got to.
+struct Node {
+    parent: Option<usize>,
+    action: Action,
+}
+
+/// The actions worth trying in a situation.
+fn actions(simulation: &Simulation) -> Vec<Action> {
+    let mut actions = vec![Action::None];
+    actions.extend(Direction::ALL.map(Action::Move));
+    actions.extend(Direction::ALL.map(Action::Snap));
+    if simulation.red_disks() > 0 {
+        actions.push(Action::DropDisk);
+    }
+    actions
+}
+
+/// Searches for the shortest sequence of actions that gets Murphy to the exit with
+/// enough infotrons.
+pub fn solve(level: &Level, limits: Limits) -> Solution {
+    let start_time = Instant::now();
+    let start = Simulation::new(level);
+    if start.murphy_index().is_none() {
+        return Solution::Unsolvable;
+    }
+    let simulation_size = start.memory_size();
+    let mut nodes = vec![Node { parent: None, action: Action::None }];
+    let mut seen = HashSet::from([start.state_hash()]);
+    let mut queue = VecDeque::from([(0, start)]);
+    while let Some((node, simulation)) = queue.pop_front() {
+        let memory = queue.len() * simulation_size
+            + nodes.len() * std::mem::size_of::<Node>()
+            + seen.len() * std::mem::size_of::<u64>() * 2;
+        if start_time.elapsed() > limits.time || memory > limits.memory {
+            return Solution::OutOfBounds;
+        }
+        for action in actions(&simulation) {
+            let mut next = simulation.clone();
+            next.step(action);
+            if next.is_murphy_dead() || !seen.insert(next.state_hash()) {
+                continue;
+            }
+            nodes.push(Node { parent: Some(node), action });
+            if next.is_won() {
+                return Solution::Solved(path(&nodes, nodes.len() - 1));
+            }
+            queue.push_back((nodes.len() - 1, next));
+        }
+    }
+    Solution::Unsolvable
+}
+
+/// The actions leading to the node from the start.
+fn path(nodes: &[Node], mut node: usize) -> Vec<Action> {
+    let mut actions = vec![];
+    while let Some(parent) = nodes[node].parent {
+        actions.push(nodes[node].action);
+        node = parent;
+    }
+    actions.reverse();
+    actions
+}
+
+/// The positions of Murphy when carrying out the actions, starting with where he starts,
+/// for showing a solution.
+pub fn murphy_path(level: &Level, actions: &[Action]) -> Vec<usize> {
+    let mut simulation = Simulation::new(level);
+    let mut positions: Vec<usize> = simulation.murphy_index().into_iter().collect();
+    for &action in actions {
+        simulation.step(action);
+        positions.extend(simulation.murphy_index().filter(|&index| positions.last() != Some(&index)));
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_file::test_level;
+
+    #[test]
+    fn corridor_with_base() {
+        let level = test_level("", &[
+            "#######",
+            "#M::IE#",
+            "#######",
+        ]);
+        assert_eq!(solve(&level, Limits::default()), Solution::Solved(vec![Action::Move(Direction::Right); 4]));
+    }
+
+    #[test]
+    fn level_full_of_base() {
+        let mut rows = vec![":".repeat(30); 12];
+        rows[1].replace_range(1..2, "M");
+        rows[6].replace_range(15..16, "I");
+        rows[10].replace_range(28..29, "E");
+        rows[0] = "#".repeat(30);
+        rows[11] = "#".repeat(30);
+        for row in &mut rows {
+            row.replace_range(0..1, "#");
+            row.replace_range(29..30, "#");
+        }
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let Solution::Solved(actions) = solve(&test_level("", &rows), Limits::default()) else {
+            panic!("not solved");
+        };
+        // Straight to the infotron and on to the exit.
+        assert_eq!(actions.len(), 14 + 5 + 13 + 4);
+    }
+}
@@ -0,0 +1,63 @@
+// Reading and writing of .SP files, which contain a single level optionally followed by
+// a demo.
+
+use std::path::Path;
+
+use crate::level::{Level, LevelError, LEVEL_SIZE, PLAY_AREA_HEIGHT, PLAY_AREA_WIDTH};
+
+pub fn from_bytes(bytes: &[u8]) -> Result<Level, LevelError> {
+    if bytes.len() < LEVEL_SIZE {
+        return Err(LevelError::InvalidSize(bytes.len()));
+    }
+    let mut level = Level::from_bytes(PLAY_AREA_WIDTH, PLAY_AREA_HEIGHT, &bytes[..LEVEL_SIZE])?;
+    level.demo = bytes[LEVEL_SIZE..].to_vec();
+    Ok(level)
+}
+
+pub fn to_bytes(level: &Level) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(LEVEL_SIZE + level.demo.len());
+    level.write_bytes(&mut bytes);
+    bytes.extend_from_slice(&level.demo);
+    bytes
+}
+
+pub fn read(path: &Path) -> Result<Level, LevelError> {
+    from_bytes(&std::fs::read(path)?)
+}
+
+pub fn write(path: &Path, level: &Level) -> Result<(), LevelError> {
+    level.check_standard_size()?;
+    Ok(std::fs::write(path, to_bytes(level))?)
+}
+
+/// Indicates whether the path has the .SP extension.
+pub fn is_sp_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("sp"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_with_demo() {
+        let mut bytes: Vec<u8> = (0..LEVEL_SIZE).map(|i| (i * 13 % 256) as u8).collect();
+        bytes.extend_from_slice(&[1, 0x70, 0x73, 0xff]);
+        let level = from_bytes(&bytes).unwrap();
+        assert_eq!(level.demo, [1, 0x70, 0x73, 0xff]);
+        assert_eq!(to_bytes(&level), bytes);
+    }
+
+    #[test]
+    fn round_trip_without_demo() {
+        let bytes: Vec<u8> = (0..LEVEL_SIZE).map(|i| (i % 41) as u8).collect();
+        let level = from_bytes(&bytes).unwrap();
+        assert!(level.demo.is_empty());
+        assert_eq!(to_bytes(&level), bytes);
+    }
+
+    #[test]
+    fn too_short() {
+        assert!(matches!(from_bytes(&[0; LEVEL_SIZE - 1]), Err(LevelError::InvalidSize(_))));
+    }
+}
@@ -1,18 +1,197 @@
+use std::borrow::Cow;
+
+use egui::{Checkbox, Color32, DragValue, ScrollArea, TextEdit, Ui};
+
+use crate::level::{is_valid_title, Level, SpecialPort, MAX_SPECIAL_PORTS, TITLE_CHARACTERS, TITLE_LENGTH};
+use crate::validate::validate;
+
+/// Colour of the things that happen as soon as the level starts, which may be intended.
+pub const WARNING_COLOR: Color32 = Color32::from_rgb(0xff, 0xa0, 0x40);
+
+/// What the user did in the status panel.
+#[derive(Default)]
+pub struct StatusResponse {
+    /// The index of the tile whose diagnostic was clicked.
+    pub clicked_tile: Option<usize>,
+    /// The level with the change made in the panel, if any.
+    pub edited_level: Option<Level>,
+}
+
 pub struct StatusPanel
 {
-    height: f32
+    height: f32,
+    title: String, // edited title, which may be invalid while typing
+    editing_title: bool,
+    infotrons_needed: Option<u8>, // while being dragged or typed
+    version: Option<u8>,
+    special_port_error: Option<String>,
 }
 
 
 impl StatusPanel {
     pub fn new(height: f32) -> Self {
-        Self { height }
+        Self {
+            height,
+            title: String::new(),
+            editing_title: false,
+            infotrons_needed: None,
+            version: None,
+            special_port_error: None,
+        }
     }
 
-    pub fn update(&self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    /// Shows the status of the level. A change of the level is returned once it's done, e.g.
+    /// when the title loses the focus, so that it can be undone as one step.
+    pub fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame, level: &Level, selected_tile_index: Option<usize>) -> StatusResponse {
+        let mut level = Cow::Borrowed(level);
+        let mut clicked_tile = None;
         egui::TopBottomPanel::new(egui::panel::TopBottomSide::Bottom, "status").show(ctx, |ui| {
             ui.heading("Status");
             ui.set_height(self.height);
+            self.show_level_info(ui, &mut level);
+            match selected_tile_index {
+                Some(index) if level.play_area[index].to_gravity_port().is_some()
+                    => self.show_special_port(ui, &mut level, index),
+                _ => self.special_port_error = None,
+            }
+            ui.separator();
+            clicked_tile = Self::show_diagnostics(ui, &level);
+        });
+        let edited_level = match level {
+            Cow::Owned(level) => Some(level),
+            Cow::Borrowed(_) => None,
+        };
+        StatusResponse { clicked_tile, edited_level }
+    }
+
+    /// Lists the problems found in the level. The ones about a tile can be clicked to
+    /// select the tile, in which case its index is returned.
+    fn show_diagnostics(ui: &mut Ui, level: &Level) -> Option<usize> {
+        let diagnostics = validate(level);
+        if diagnostics.is_empty() {
+            ui.label("No problems found");
+            return None;
+        }
+        let mut clicked_tile = None;
+        ScrollArea::vertical().id_source("diagnostics").show(ui, |ui| {
+            for diagnostic in diagnostics {
+                let color = if diagnostic.problem.is_warning() { WARNING_COLOR } else { Color32::YELLOW };
+                match diagnostic.tile_index {
+                    Some(index) => {
+                        let (col, row) = level.col_row(index);
+                        let text = format!("({}, {}): {}", col, row, diagnostic.problem);
+                        if ui.link(egui::RichText::new(text).color(color)).clicked() {
+                            clicked_tile = Some(index);
+                        }
+                    }
+                    None => { ui.colored_label(color, diagnostic.problem.to_string()); }
+                }
+            }
+        });
+        clicked_tile
+    }
+
+    fn show_level_info(&mut self, ui: &mut Ui, level: &mut Cow<Level>) {
+        if !self.editing_title {
+            self.title = level.info.title().trim_end().to_owned();
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Title");
+            let response = ui.add(TextEdit::singleline(&mut self.title).desired_width(200.));
+            self.editing_title = response.has_focus();
+            if response.changed() {
+                self.title = self.title.to_uppercase();
+            }
+            if response.lost_focus() && is_valid_title(&self.title) && self.title != level.info.title().trim_end() {
+                level.to_mut().info.set_title(&self.title).unwrap();
+            }
+            if !is_valid_title(&self.title) {
+                ui.colored_label(Color32::RED, format!("At most {} characters of: {}", TITLE_LENGTH, TITLE_CHARACTERS));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let mut gravity = level.info.gravity();
+            if ui.checkbox(&mut gravity, "Gravity").changed() {
+                level.to_mut().info.set_gravity(gravity);
+            }
+
+            let mut freeze_zonks = level.info.freeze_zonks();
+            if ui.checkbox(&mut freeze_zonks, "Freeze zonks").changed() {
+                level.to_mut().info.set_freeze_zonks(freeze_zonks);
+            }
+
+            ui.separator();
+            ui.label("Infotrons needed");
+            if let Some(count) = Self::edit_number(ui, &mut self.infotrons_needed, level.info.infotrons_needed(), "0 = all") {
+                level.to_mut().info.set_infotrons_needed(count);
+            }
+
+            ui.separator();
+            ui.label(format!("Size {}x{}", level.width(), level.height()));
+
+            ui.separator();
+            ui.label("Version");
+            if let Some(version) = Self::edit_number(ui, &mut self.version, level.info.version(), "") {
+                level.to_mut().info.set_version(version);
+            }
+        });
+    }
+
+    /// Shows a drag value for the number. The number being dragged or typed is kept in
+    /// `pending`, and returned when the drag value is let go of.
+    fn edit_number(ui: &mut Ui, pending: &mut Option<u8>, number: u8, hint: &str) -> Option<u8> {
+        let mut value = pending.unwrap_or(number);
+        let mut response = ui.add(DragValue::new(&mut value));
+        if !hint.is_empty() {
+            response = response.on_hover_text(hint);
+        }
+        if response.changed() {
+            *pending = Some(value);
+        }
+        if response.dragged() || response.has_focus() {
+            return None;
+        }
+        pending.take().filter(|&value| value != number)
+    }
+
+    /// Shows the special port table entry of the selected one-way port.
+    fn show_special_port(&mut self, ui: &mut Ui, level: &mut Cow<Level>, index: usize) {
+        let port_count = level.info.special_ports().len();
+        ui.horizontal(|ui| {
+            let (col, row) = level.col_row(index);
+            ui.label(format!("Port at ({}, {})", col, row));
+
+            let mut special = level.special_port(index).is_some();
+            let can_toggle = special || port_count < MAX_SPECIAL_PORTS;
+            if ui.add_enabled(can_toggle, Checkbox::new(&mut special, "Special")).changed() {
+                if special {
+                    self.special_port_error = level.to_mut().set_special_port(SpecialPort::new(index)).err().map(|e| e.to_string());
+                }
+                else {
+                    level.to_mut().remove_special_port(index);
+                    self.special_port_error = None;
+                }
+            }
+
+            if let Some(mut port) = level.special_port(index) {
+                let mut changed = ui.checkbox(&mut port.gravity, "Gravity").changed();
+                changed |= ui.checkbox(&mut port.freeze_zonks, "Freeze zonks").changed();
+                changed |= ui.checkbox(&mut port.freeze_enemies, "Freeze enemies").changed();
+                if changed {
+                    self.special_port_error = level.to_mut().set_special_port(port).err().map(|e| e.to_string());
+                }
+            }
+
+            ui.separator();
+            ui.label(format!("{}/{} special ports", port_count, MAX_SPECIAL_PORTS));
+            if !can_toggle {
+                ui.colored_label(Color32::RED, format!("A level can have at most {} special ports", MAX_SPECIAL_PORTS));
+            }
+            if let Some(error) = &self.special_port_error {
+                ui.colored_label(Color32::RED, error);
+            }
         });
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,410 @@
+// A plain text format for levels, meant for keeping levels in version control and editing
+// them with a text editor. A file contains any number of levels:
+//
+//   level 1
+//   title = "------- WARM UP -------"
+//   size = 60x24
+//   gravity = 0
+//   freeze_zonks = 0
+//   infotrons_needed = 0
+//   version = 32
+//   reserved = 0000000000000000
+//   special_port = 12 3 1 0 0
+//   unknown_tile = 5 7 42
+//   demo = 0102ff
+//   tiles
+//   1111111111...
+//   1M:::I:::....
+//
+// Each tile is one character, see `TILE_CHARACTERS`. Tiles unknown to the editor are shown
+// as '?' and their values are listed as `unknown_tile = col row value`. Special ports are
+// `col row gravity freeze_zonks freeze_enemies`, optionally followed by the unused last
+// byte of the entry. The reserved bytes are the parts of the info block the editor doesn't
+// know about.
+//
+// The numbers are the bytes stored in the level, e.g. freeze_zonks = 2 freezes the zonks,
+// so that converting a level to text and back gives the same bytes. Titles with characters
+// that can't be written between quotes are written as `title_bytes = <hex>` instead, and
+// special port tables the special_port lines can't describe, e.g. ones with leftovers past
+// the used entries, as `special_ports = <hex of the count and the table>`.
+
+use std::path::Path;
+
+use crate::level::{Level, LevelError, LevelInfo, INFO_SIZE, TITLE_LENGTH, MAX_SPECIAL_PORTS};
+use crate::level::{FREEZE_ZONKS_OFFSET, GRAVITY_OFFSET, INFOTRONS_NEEDED_OFFSET, SPECIAL_PORTS_OFFSET};
+use crate::level::{SPECIAL_PORT_COUNT_OFFSET, SPECIAL_PORT_SIZE, TITLE_OFFSET, VERSION_OFFSET};
+use crate::tile::Tile;
+
+/// Characters of the tiles, indexed by the byte values.
+pub const TILE_CHARACTERS: [char; 40] = [
+    '.', 'O', ':', 'M', 'I', 'R', '#', 'E', 'o', '>',
+    'v', '<', '^', '}', 'W', '{', 'A', 'S', 'y', 'T',
+    'r', '|', '-', '+', 'e', 'B', '(', ')', '1', '2',
+    '3', '4', '5', '6', '7', '8', '9', '0', 'n', 'u',
+];
+
+const UNKNOWN_CHARACTER: char = '?';
+
+// Bytes of the info block not covered by the other fields.
+const RESERVED: [std::ops::Range<usize>; 2] = [0..4, 92..96];
+
+// The special port count followed by the table.
+const SPECIAL_PORT_TABLE: std::ops::Range<usize> = SPECIAL_PORT_COUNT_OFFSET..SPECIAL_PORTS_OFFSET + MAX_SPECIAL_PORTS * SPECIAL_PORT_SIZE;
+
+/// Indicates whether the byte can be written in a quoted title.
+fn is_title_byte(byte: u8) -> bool {
+    (b' '..=b'~').contains(&byte) && byte != b'"'
+}
+
+fn tile_char(tile: Tile) -> char {
+    match tile {
+        Tile::Unknown(_) => UNKNOWN_CHARACTER,
+        known => TILE_CHARACTERS[known.byte() as usize],
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// The special_port lines describing the special port table, unless it holds something
+/// they can't: more entries than fit, positions that aren't tiles of the level, or leftovers
+/// past the used entries.
+fn special_port_lines(level: &Level) -> Option<Vec<String>> {
+    let info = level.info.bytes();
+    let count = info[SPECIAL_PORT_COUNT_OFFSET] as usize;
+    let table = &info[SPECIAL_PORTS_OFFSET..SPECIAL_PORT_TABLE.end];
+    if count > MAX_SPECIAL_PORTS || table[count * SPECIAL_PORT_SIZE..].iter().any(|&b| b != 0) {
+        return None;
+    }
+    table.chunks_exact(SPECIAL_PORT_SIZE).take(count).map(|entry| {
+        let offset = u16::from_be_bytes([entry[0], entry[1]]) as usize;
+        if !offset.is_multiple_of(2) || offset / 2 >= level.play_area.len() {
+            return None;
+        }
+        let (col, row) = level.col_row(offset / 2);
+        let mut line = format!("special_port = {} {} {} {} {}", col, row, entry[2], entry[3], entry[4]);
+        if entry[5] != 0 {
+            line.push_str(&format!(" {}", entry[5]));
+        }
+        Some(line)
+    }).collect()
+}
+
+fn write_level(out: &mut String, number: usize, level: &Level) {
+    let info = level.info.bytes();
+    out.push_str(&format!("level {}\n", number));
+    let title = &info[TITLE_OFFSET..TITLE_OFFSET + TITLE_LENGTH];
+    if title.iter().all(|&b| is_title_byte(b)) {
+        out.push_str(&format!("title = \"{}\"\n", level.info.title()));
+    }
+    else {
+        out.push_str(&format!("title_bytes = {}\n", to_hex(title)));
+    }
+    out.push_str(&format!("size = {}x{}\n", level.width(), level.height()));
+    out.push_str(&format!("gravity = {}\n", info[GRAVITY_OFFSET]));
+    out.push_str(&format!("freeze_zonks = {}\n", info[FREEZE_ZONKS_OFFSET]));
+    out.push_str(&format!("infotrons_needed = {}\n", info[INFOTRONS_NEEDED_OFFSET]));
+    out.push_str(&format!("version = {}\n", info[VERSION_OFFSET]));
+    let reserved: Vec<u8> = RESERVED.iter().flat_map(|range| info[range.clone()].to_vec()).collect();
+    out.push_str(&format!("reserved = {}\n", to_hex(&reserved)));
+    match special_port_lines(level) {
+        Some(lines) => lines.iter().for_each(|line| { out.push_str(line); out.push('\n'); }),
+        None => out.push_str(&format!("special_ports = {}\n", to_hex(&info[SPECIAL_PORT_TABLE]))),
+    }
+    for (i, tile) in level.play_area.iter().enumerate() {
+        if let Tile::Unknown(value) = tile {
+            let (col, row) = level.col_row(i);
+            out.push_str(&format!("unknown_tile = {} {} {}\n", col, row, value));
+        }
+    }
+    if !level.demo.is_empty() {
+        out.push_str(&format!("demo = {}\n", to_hex(&level.demo)));
+    }
+    out.push_str("tiles\n");
+    for row in level.play_area.chunks(level.width()) {
+        out.extend(row.iter().map(|&tile| tile_char(tile)));
+        out.push('\n');
+    }
+}
+
+pub fn to_string(levels: &[Level]) -> String {
+    let mut out = String::new();
+    for (i, level) in levels.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        write_level(&mut out, i + 1, level);
+    }
+    out
+}
+
+/// The fields of a level read so far, until the tiles are reached.
+#[derive(Default)]
+struct LevelFields {
+    title: Option<Vec<u8>>,
+    size: Option<(usize, usize)>,
+    gravity: u8,
+    freeze_zonks: u8,
+    infotrons_needed: u8,
+    version: u8,
+    reserved: Vec<u8>,
+    special_ports: Vec<(usize, [usize; 2], [u8; 4])>, // line, col and row, the other bytes of the entry
+    special_port_table: Option<(usize, Vec<u8>)>, // line, count and table
+    unknown_tiles: Vec<(usize, [usize; 2], u8)>, // line, col and row, value
+    demo: Vec<u8>,
+}
+
+fn parse_error(line: usize, message: impl Into<String>) -> LevelError {
+    LevelError::Parse { line, message: message.into() }
+}
+
+fn parse_numbers<const N: usize>(value: &str, line: usize) -> Result<[usize; N], LevelError> {
+    let numbers: Vec<usize> = value.split_whitespace().map(str::parse).collect::<Result<_, _>>()
+        .map_err(|_| parse_error(line, format!("invalid numbers {:?}", value)))?;
+    numbers.try_into().map_err(|_| parse_error(line, format!("expected {} numbers", N)))
+}
+
+impl LevelFields {
+    fn set(&mut self, key: &str, value: &str, line: usize) -> Result<(), LevelError> {
+        let number = || value.parse::<u8>().map_err(|_| parse_error(line, format!("invalid number {:?}", value)));
+        match key {
+            "title" => {
+                let title = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+                    .ok_or_else(|| parse_error(line, "expected a quoted title"))?;
+                if title.len() > TITLE_LENGTH || !title.bytes().all(is_title_byte) {
+                    return Err(parse_error(line, format!("invalid title {:?}", title)));
+                }
+                self.title = Some(title.as_bytes().to_vec());
+            }
+            "title_bytes" => self.title = Some(from_hex(value)
+                .filter(|bytes| bytes.len() <= TITLE_LENGTH)
+                .ok_or_else(|| parse_error(line, "invalid title bytes"))?),
+            "size" => {
+                let (width, height) = value.split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .filter(|&(w, h)| w > 0 && h > 0)
+                    .ok_or_else(|| parse_error(line, format!("invalid size {:?}", value)))?;
+                self.size = Some((width, height));
+            }
+            "gravity" => self.gravity = number()?,
+            "freeze_zonks" => self.freeze_zonks = number()?,
+            "infotrons_needed" => self.infotrons_needed = number()?,
+            "version" => self.version = number()?,
+            "reserved" => self.reserved = from_hex(value)
+                .filter(|bytes| bytes.len() == RESERVED.iter().map(|r| r.len()).sum())
+                .ok_or_else(|| parse_error(line, "invalid reserved bytes"))?,
+            "special_port" => {
+                let numbers = match parse_numbers::<6>(value, line) {
+                    Ok(numbers) => numbers,
+                    Err(_) => {
+                        let [col, row, gravity, freeze_zonks, freeze_enemies] = parse_numbers(value, line)?;
+                        [col, row, gravity, freeze_zonks, freeze_enemies, 0]
+                    }
+                };
+                let [col, row, bytes @ ..] = numbers;
+                if bytes.iter().any(|&byte| byte > u8::MAX as usize) {
+                    return Err(parse_error(line, "invalid special port"));
+                }
+                self.special_ports.push((line, [col, row], bytes.map(|byte| byte as u8)));
+            }
+            "special_ports" => self.special_port_table = Some((line, from_hex(value)
+                .filter(|bytes| bytes.len() == SPECIAL_PORT_TABLE.len())
+                .ok_or_else(|| parse_error(line, "invalid special port table"))?)),
+            "unknown_tile" => {
+                let [col, row, value] = parse_numbers(value, line)?;
+                let value = u8::try_from(value).map_err(|_| parse_error(line, "invalid tile value"))?;
+                self.unknown_tiles.push((line, [col, row], value));
+            }
+            "demo" => self.demo = from_hex(value).ok_or_else(|| parse_error(line, "invalid demo"))?,
+            _ => return Err(parse_error(line, format!("unknown field {:?}", key))),
+        }
+        Ok(())
+    }
+
+    fn into_level(self, rows: &[(usize, &str)]) -> Result<Level, LevelError> {
+        let (width, height) = self.size.ok_or_else(|| parse_error(rows.first().map_or(0, |r| r.0), "missing size"))?;
+        let mut level = Level::new(width, height);
+        let mut info = [0u8; INFO_SIZE];
+        for (range, &byte) in RESERVED.iter().flat_map(|range| range.clone()).zip(&self.reserved) {
+            info[range] = byte;
+        }
+        let title = &mut info[TITLE_OFFSET..TITLE_OFFSET + TITLE_LENGTH];
+        title.fill(b' ');
+        let title_bytes = self.title.unwrap_or_default();
+        title[..title_bytes.len()].copy_from_slice(&title_bytes);
+        info[GRAVITY_OFFSET] = self.gravity;
+        info[FREEZE_ZONKS_OFFSET] = self.freeze_zonks;
+        info[INFOTRONS_NEEDED_OFFSET] = self.infotrons_needed;
+        info[VERSION_OFFSET] = self.version;
+        level.demo = self.demo;
+
+        let mut unknown = vec![]; // the line and index of each '?'
+        for (row, &(line, text)) in rows.iter().enumerate() {
+            if text.chars().count() != width {
+                return Err(parse_error(line, format!("expected {} tiles", width)));
+            }
+            for (col, c) in text.chars().enumerate() {
+                let index = level.index(col, row);
+                level.play_area[index] = match TILE_CHARACTERS.iter().position(|&t| t == c) {
+                    Some(byte) => Tile::ALL[byte],
+                    None if c == UNKNOWN_CHARACTER => {
+                        unknown.push((line, index));
+                        Tile::Unknown(0)
+                    }
+                    None => return Err(parse_error(line, format!("unknown tile {:?}", c))),
+                };
+            }
+        }
+        let position = |line: usize, [col, row]: [usize; 2]| match col < width && row < height {
+            true => Ok(col + row * width),
+            false => Err(parse_error(line, format!("({}, {}) is outside the level", col, row))),
+        };
+        let mut has_value = vec![false; level.play_area.len()];
+        for (line, col_row, value) in self.unknown_tiles {
+            let index = position(line, col_row)?;
+            level.play_area[index] = Tile::from_byte(value);
+            has_value[index] = true;
+        }
+        if let Some(&(line, index)) = unknown.iter().find(|&&(_, index)| !has_value[index]) {
+            let (col, row) = level.col_row(index);
+            return Err(parse_error(line, format!("missing unknown_tile for the '?' at ({}, {})", col, row)));
+        }
+
+        match self.special_port_table {
+            Some((line, _)) if !self.special_ports.is_empty()
+                => return Err(parse_error(line, "special_ports can't be combined with special_port lines")),
+            Some((_, table)) => info[SPECIAL_PORT_TABLE].copy_from_slice(&table),
+            None => {
+                if self.special_ports.len() > MAX_SPECIAL_PORTS {
+                    return Err(LevelError::TooManySpecialPorts);
+                }
+                info[SPECIAL_PORT_COUNT_OFFSET] = self.special_ports.len() as u8;
+                let table = &mut info[SPECIAL_PORTS_OFFSET..SPECIAL_PORT_TABLE.end];
+                for ((line, col_row, bytes), entry) in self.special_ports.into_iter().zip(table.chunks_exact_mut(SPECIAL_PORT_SIZE)) {
+                    let offset = (position(line, col_row)? * 2) as u16;
+                    entry[..2].copy_from_slice(&offset.to_be_bytes());
+                    entry[2..].copy_from_slice(&bytes);
+                }
+            }
+        }
+        level.info = LevelInfo::from_bytes(info);
+        Ok(level)
+    }
+}
+
+pub fn from_str(text: &str) -> Result<Vec<Level>, LevelError> {
+    let mut levels = vec![];
+    let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line.trim_end()));
+    let mut fields: Option<LevelFields> = None;
+    while let Some((line, text)) = lines.next() {
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        if text.starts_with("level") {
+            fields = Some(LevelFields::default());
+        }
+        else if text == "tiles" {
+            let level_fields = fields.take().ok_or_else(|| parse_error(line, "tiles before level"))?;
+            let height = level_fields.size.map_or(0, |(_, h)| h);
+            let rows: Vec<(usize, &str)> = lines.by_ref().take(height).collect();
+            if rows.len() != height {
+                return Err(parse_error(line, format!("expected {} rows of tiles", height)));
+            }
+            levels.push(level_fields.into_level(&rows)?);
+        }
+        else {
+            let level_fields = fields.as_mut().ok_or_else(|| parse_error(line, "expected level"))?;
+            let (key, value) = text.split_once('=').ok_or_else(|| parse_error(line, "expected key = value"))?;
+            level_fields.set(key.trim(), value.trim(), line)?;
+        }
+    }
+    if fields.is_some() {
+        return Err(parse_error(text.lines().count(), "missing tiles"));
+    }
+    Ok(levels)
+}
+
+pub fn read(path: &Path) -> Result<Vec<Level>, LevelError> {
+    from_str(&std::fs::read_to_string(path)?)
+}
+
+pub fn write(path: &Path, levels: &[Level]) -> Result<(), LevelError> {
+    Ok(std::fs::write(path, to_string(levels))?)
+}
+
+/// Parses a level from rows of tile characters, with the other fields as the lines of
+/// `fields`, for tests.
+#[cfg(test)]
+pub(crate) fn test_level(fields: &str, rows: &[&str]) -> Level {
+    let text = format!("level 1\nsize = {}x{}\n{}\ntiles\n{}\n", rows[0].len(), rows.len(), fields, rows.join("\n"));
+    from_str(&text).unwrap().remove(0)
+}
+
+/// Indicates whether the path has the .TXT extension.
+pub fn is_text_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("txt"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{SpecialPort, LEVEL_SIZE};
+    use crate::levels_dat::{self, LEVEL_COUNT};
+
+    /// Converts the levels to text and back, checking that the bytes stay the same.
+    fn assert_round_trip(levels: &[Level]) {
+        let read = from_str(&to_string(levels)).unwrap();
+        assert_eq!(read.len(), levels.len());
+        for (read, level) in read.iter().zip(levels) {
+            assert_eq!((read.width(), read.height()), (level.width(), level.height()));
+            assert_eq!(read.demo, level.demo);
+        }
+        assert_eq!(levels_dat::to_bytes(&read), levels_dat::to_bytes(levels));
+    }
+
+    #[test]
+    fn round_trip_of_any_bytes() {
+        let bytes: Vec<u8> = (0..LEVEL_COUNT * LEVEL_SIZE).map(|i| (i * 7 % 251) as u8).collect();
+        let levels = levels_dat::from_bytes(&bytes).unwrap();
+        let text = to_string(&levels);
+        assert!(text.contains("title_bytes = ") && text.contains("special_ports = "));
+        assert_round_trip(&levels);
+    }
+
+    #[test]
+    fn round_trip_of_raw_info_values() {
+        let mut level = Level::default();
+        level.play_area[0] = Tile::Unknown(200);
+        level.play_area[61] = Tile::PortRight;
+        level.info.set_special_ports(&[SpecialPort { gravity: true, ..SpecialPort::new(61) }]).unwrap();
+        level.demo = vec![1, 0x70, 0xff];
+        let mut info = *level.info.bytes();
+        info[TITLE_OFFSET..TITLE_OFFSET + 5].copy_from_slice(b"lower");
+        info[GRAVITY_OFFSET] = 5;
+        info[FREEZE_ZONKS_OFFSET] = 1;
+        info[SPECIAL_PORTS_OFFSET + 5] = 9;
+        level.info = LevelInfo::from_bytes(info);
+        let text = to_string(std::slice::from_ref(&level));
+        assert!(text.contains("title = \"lower"));
+        assert!(text.contains("special_port = 1 1 1 0 0 9\n"));
+        assert_round_trip(&[level]);
+    }
+
+    #[test]
+    fn unknown_tile_without_value() {
+        let mut level = Level::new(3, 1);
+        level.play_area[1] = Tile::Unknown(77);
+        let text = to_string(&[level]);
+        assert!(from_str(&text).is_ok());
+        let text = text.replace("unknown_tile = 1 0 77\n", "");
+        assert!(matches!(from_str(&text), Err(LevelError::Parse { .. })));
+    }
+}
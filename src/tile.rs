@@ -0,0 +1,327 @@
+// The tiles of the play area and their byte values in the level files.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Tile {
+    Empty = 0,
+    Zonk = 1,
+    Base = 2,
+    Murphy = 3,
+    Infotron = 4,
+    RamChip = 5,
+    Wall = 6,
+    Exit = 7,
+    FloppyOrange = 8,
+    PortRight = 9,
+    PortDown = 10,
+    PortLeft = 11,
+    PortUp = 12,
+    GravityPortRight = 13,
+    GravityPortDown = 14,
+    GravityPortLeft = 15,
+    GravityPortUp = 16,
+    SnikSnak = 17,
+    FloppyYellow = 18,
+    Terminal = 19,
+    FloppyRed = 20,
+    Port2WayVertical = 21,
+    Port2WayHorizontal = 22,
+    Port4Way = 23,
+    Electron = 24,
+    Bug = 25,
+    RamLeft = 26,
+    RamRight = 27,
+    Hardware1 = 28,
+    Hardware2 = 29,
+    Hardware3 = 30,
+    Hardware4 = 31,
+    Hardware5 = 32,
+    Hardware6 = 33,
+    Hardware7 = 34,
+    Hardware8 = 35,
+    Hardware9 = 36,
+    Hardware10 = 37,
+    RamTop = 38,
+    RamBottom = 39,
+    /// A byte the editor doesn't know about, e.g. from a custom level. Kept as is so that
+    /// it's written back unchanged.
+    Unknown(u8),
+}
+
+impl Tile {
+    /// All tiles, in the order of their byte values in the level files.
+    pub const ALL: [Tile; 40] = [
+        Tile::Empty, Tile::Zonk, Tile::Base, Tile::Murphy, Tile::Infotron,
+        Tile::RamChip, Tile::Wall, Tile::Exit, Tile::FloppyOrange, Tile::PortRight,
+        Tile::PortDown, Tile::PortLeft, Tile::PortUp, Tile::GravityPortRight, Tile::GravityPortDown,
+        Tile::GravityPortLeft, Tile::GravityPortUp, Tile::SnikSnak, Tile::FloppyYellow, Tile::Terminal,
+        Tile::FloppyRed, Tile::Port2WayVertical, Tile::Port2WayHorizontal, Tile::Port4Way, Tile::Electron,
+        Tile::Bug, Tile::RamLeft, Tile::RamRight, Tile::Hardware1, Tile::Hardware2,
+        Tile::Hardware3, Tile::Hardware4, Tile::Hardware5, Tile::Hardware6, Tile::Hardware7,
+        Tile::Hardware8, Tile::Hardware9, Tile::Hardware10, Tile::RamTop, Tile::RamBottom,
+    ];
+
+    /// Converts a byte from a level file to a tile, keeping unknown bytes as `Tile::Unknown`.
+    pub fn from_byte(value: u8) -> Self {
+        Tile::try_from(value).unwrap_or(Tile::Unknown(value))
+    }
+
+    /// The byte value of the tile in the level files.
+    pub fn byte(self) -> u8 {
+        match self {
+            Tile::Unknown(value) => value,
+            known => Tile::ALL.iter().position(|&tile| tile == known).unwrap() as u8,
+        }
+    }
+}
+
+impl Tile {
+    /// Indicates whether the tile is one of the indestructible hardware tiles that the
+    /// border of a level is made of.
+    pub fn is_hardware(self) -> bool {
+        matches!(self, Tile::Wall | Tile::Hardware1 | Tile::Hardware2 | Tile::Hardware3 | Tile::Hardware4
+            | Tile::Hardware5 | Tile::Hardware6 | Tile::Hardware7 | Tile::Hardware8 | Tile::Hardware9 | Tile::Hardware10)
+    }
+
+    pub fn is_gravity_port(self) -> bool {
+        matches!(self, Tile::GravityPortRight | Tile::GravityPortDown | Tile::GravityPortLeft | Tile::GravityPortUp)
+    }
+
+    /// The gravity port pointing to the same direction as this one-way port.
+    pub fn to_gravity_port(self) -> Option<Tile> {
+        match self {
+            Tile::PortRight | Tile::GravityPortRight => Some(Tile::GravityPortRight),
+            Tile::PortDown | Tile::GravityPortDown => Some(Tile::GravityPortDown),
+            Tile::PortLeft | Tile::GravityPortLeft => Some(Tile::GravityPortLeft),
+            Tile::PortUp | Tile::GravityPortUp => Some(Tile::GravityPortUp),
+            _ => None
+        }
+    }
+
+    /// The next tile of the same kind, e.g. the next disk colour, for cycling through the
+    /// variants. Tiles without variants are returned as is.
+    pub fn next_variant(self) -> Tile {
+        match self {
+            Tile::FloppyYellow => Tile::FloppyRed,
+            Tile::FloppyRed => Tile::FloppyOrange,
+            Tile::FloppyOrange => Tile::FloppyYellow,
+            Tile::Base => Tile::Bug,
+            Tile::Bug => Tile::Base,
+            Tile::Wall => Tile::Hardware1,
+            Tile::Hardware10 => Tile::Wall,
+            hardware if hardware.is_hardware() => Tile::ALL[hardware.byte() as usize + 1],
+            Tile::RamChip => Tile::RamLeft,
+            Tile::RamLeft => Tile::RamRight,
+            Tile::RamRight => Tile::RamTop,
+            Tile::RamTop => Tile::RamBottom,
+            Tile::RamBottom => Tile::RamChip,
+            port if port.is_gravity_port() => port.to_regular_port().unwrap(),
+            port => port.to_gravity_port().unwrap_or(port),
+        }
+    }
+
+    /// The tile turned 90 degrees clockwise.
+    pub fn rotated(self) -> Tile {
+        self.transformed(Transform::RotateClockwise)
+    }
+
+    /// The tile as it looks after mirroring or turning the part of the level it's in, so
+    /// that ports keep pointing the same way relative to their surroundings. Only ports and
+    /// the halves of two-tile RAM chips have a direction, other tiles are returned as is.
+    pub fn transformed(self, transform: Transform) -> Tile {
+        match DIRECTIONAL_TILES.iter().find(|entry| entry[0] == self) {
+            Some(entry) => entry[transform as usize + 1],
+            None => self,
+        }
+    }
+
+    /// The other half of a two-tile RAM chip: its offset in columns and rows from this half,
+    /// and the tile it should be.
+    pub fn ram_chip_partner(self) -> Option<((isize, isize), Tile)> {
+        match self {
+            Tile::RamLeft => Some(((1, 0), Tile::RamRight)),
+            Tile::RamRight => Some(((-1, 0), Tile::RamLeft)),
+            Tile::RamTop => Some(((0, 1), Tile::RamBottom)),
+            Tile::RamBottom => Some(((0, -1), Tile::RamTop)),
+            _ => None,
+        }
+    }
+
+    /// The regular port pointing to the same direction as this one-way port.
+    pub fn to_regular_port(self) -> Option<Tile> {
+        match self {
+            Tile::PortRight | Tile::GravityPortRight => Some(Tile::PortRight),
+            Tile::PortDown | Tile::GravityPortDown => Some(Tile::PortDown),
+            Tile::PortLeft | Tile::GravityPortLeft => Some(Tile::PortLeft),
+            Tile::PortUp | Tile::GravityPortUp => Some(Tile::PortUp),
+            _ => None
+        }
+    }
+}
+
+/// A way of mirroring or turning a part of a level.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transform {
+    FlipHorizontal = 0,
+    FlipVertical = 1,
+    RotateClockwise = 2,
+}
+
+/// The tiles that have a direction, and what they become when flipped horizontally, flipped
+/// vertically and rotated clockwise, in the order of `Transform`.
+const DIRECTIONAL_TILES: [[Tile; 4]; 14] = [
+    [Tile::PortRight, Tile::PortLeft, Tile::PortRight, Tile::PortDown],
+    [Tile::PortDown, Tile::PortDown, Tile::PortUp, Tile::PortLeft],
+    [Tile::PortLeft, Tile::PortRight, Tile::PortLeft, Tile::PortUp],
+    [Tile::PortUp, Tile::PortUp, Tile::PortDown, Tile::PortRight],
+    [Tile::GravityPortRight, Tile::GravityPortLeft, Tile::GravityPortRight, Tile::GravityPortDown],
+    [Tile::GravityPortDown, Tile::GravityPortDown, Tile::GravityPortUp, Tile::GravityPortLeft],
+    [Tile::GravityPortLeft, Tile::GravityPortRight, Tile::GravityPortLeft, Tile::GravityPortUp],
+    [Tile::GravityPortUp, Tile::GravityPortUp, Tile::GravityPortDown, Tile::GravityPortRight],
+    [Tile::Port2WayVertical, Tile::Port2WayVertical, Tile::Port2WayVertical, Tile::Port2WayHorizontal],
+    [Tile::Port2WayHorizontal, Tile::Port2WayHorizontal, Tile::Port2WayHorizontal, Tile::Port2WayVertical],
+    [Tile::RamLeft, Tile::RamRight, Tile::RamLeft, Tile::RamTop],
+    [Tile::RamRight, Tile::RamLeft, Tile::RamRight, Tile::RamBottom],
+    [Tile::RamTop, Tile::RamTop, Tile::RamBottom, Tile::RamRight],
+    [Tile::RamBottom, Tile::RamBottom, Tile::RamTop, Tile::RamLeft],
+];
+
+impl TryFrom<u8> for Tile {
+    type Error = u8;
+
+    /// Converts a byte from a level file to a known tile. Fails with the offending byte if it
+    /// doesn't correspond to any known tile.
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Tile::ALL.get(value as usize).copied().ok_or(value)
+    }
+}
+
+impl From<Tile> for u8 {
+    fn from(tile: Tile) -> Self {
+        tile.byte()
+    }
+}
+
+/// Names of the images of the tiles in the img directory, indexed by the byte values.
+pub const TILE_IMAGES:[&str; 40] = [
+    "empty.png",
+    "zonk.png",
+    "base.png",
+    "murphy.png",
+    "info.png",
+    "ram.png",
+    "wall.png",
+    "exit.png",
+    "disk-orange.png",
+    "port-lr.png",
+    "port-ud.png",
+    "port-rl.png",
+    "port-du.png",
+    "gport-lr.png",
+    "gport-ud.png",
+    "gport-rl.png",
+    "gport-du.png",
+    "ssnak.png",
+    "disk-yellow.png",
+    "terminal.png",
+    "disk-red.png",
+    "port-v.png",
+    "port-h.png",
+    "port-x.png",
+    "electron.png",
+    "bug.png",
+    "ramh.png",
+    "ramright.png",
+    "hw1.png",
+    "hw2.png",
+    "hw3.png",
+    "hw4.png",
+    "hw5.png",
+    "hw6.png",
+    "hw7.png",
+    "hw8.png",
+    "hw9.png",
+    "hw10.png",
+    "ramv.png",
+    "rambottom.png",
+];
+
+/// The files named in `TILE_IMAGES`, built into the executables so that they can be started
+/// from any directory.
+pub const TILE_IMAGE_BYTES: [&[u8]; 40] = [
+    include_bytes!("../img/empty.png"),
+    include_bytes!("../img/zonk.png"),
+    include_bytes!("../img/base.png"),
+    include_bytes!("../img/murphy.png"),
+    include_bytes!("../img/info.png"),
+    include_bytes!("../img/ram.png"),
+    include_bytes!("../img/wall.png"),
+    include_bytes!("../img/exit.png"),
+    include_bytes!("../img/disk-orange.png"),
+    include_bytes!("../img/port-lr.png"),
+    include_bytes!("../img/port-ud.png"),
+    include_bytes!("../img/port-rl.png"),
+    include_bytes!("../img/port-du.png"),
+    include_bytes!("../img/gport-lr.png"),
+    include_bytes!("../img/gport-ud.png"),
+    include_bytes!("../img/gport-rl.png"),
+    include_bytes!("../img/gport-du.png"),
+    include_bytes!("../img/ssnak.png"),
+    include_bytes!("../img/disk-yellow.png"),
+    include_bytes!("../img/terminal.png"),
+    include_bytes!("../img/disk-red.png"),
+    include_bytes!("../img/port-v.png"),
+    include_bytes!("../img/port-h.png"),
+    include_bytes!("../img/port-x.png"),
+    include_bytes!("../img/electron.png"),
+    include_bytes!("../img/bug.png"),
+    include_bytes!("../img/ramh.png"),
+    include_bytes!("../img/ramright.png"),
+    include_bytes!("../img/hw1.png"),
+    include_bytes!("../img/hw2.png"),
+    include_bytes!("../img/hw3.png"),
+    include_bytes!("../img/hw4.png"),
+    include_bytes!("../img/hw5.png"),
+    include_bytes!("../img/hw6.png"),
+    include_bytes!("../img/hw7.png"),
+    include_bytes!("../img/hw8.png"),
+    include_bytes!("../img/hw9.png"),
+    include_bytes!("../img/hw10.png"),
+    include_bytes!("../img/ramv.png"),
+    include_bytes!("../img/rambottom.png"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transformed_times(tile: Tile, transform: Transform, times: usize) -> Tile {
+        (0..times).fold(tile, |tile, _| tile.transformed(transform))
+    }
+
+    #[test]
+    fn rotating_four_times_is_identity() {
+        for tile in Tile::ALL {
+            assert_eq!(transformed_times(tile, Transform::RotateClockwise, 4), tile);
+        }
+    }
+
+    #[test]
+    fn flipping_twice_is_identity() {
+        for tile in Tile::ALL {
+            assert_eq!(transformed_times(tile, Transform::FlipHorizontal, 2), tile);
+            assert_eq!(transformed_times(tile, Transform::FlipVertical, 2), tile);
+        }
+    }
+
+    #[test]
+    fn ram_chip_halves_stay_partners() {
+        for tile in Tile::ALL {
+            let Some((_, partner)) = tile.ram_chip_partner() else { continue };
+            for transform in [Transform::FlipHorizontal, Transform::FlipVertical, Transform::RotateClockwise] {
+                let (_, transformed_partner) = tile.transformed(transform).ram_chip_partner().unwrap();
+                assert_eq!(transformed_partner, partner.transformed(transform));
+            }
+        }
+    }
+}
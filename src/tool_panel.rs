@@ -6,54 +6,40 @@ use std::rc::Rc;
 use egui::{ImageButton, vec2, Layout, Ui, Align, Context, ScrollArea};
 use egui_extras::RetainedImage;
 
-use crate::images::{Images};
+use crate::images::Images;
+use crate::level::Level;
+use crate::tile::Tile;
 
+/// The tiles placed by a drawing tool: a single tile, or both halves of a two-tile RAM chip.
 #[derive(Copy, Clone, Debug, PartialEq)]
-pub enum Tile {
-    Empty = 0,
-    Zonk = 1,
-    Base = 2,
-    Murphy = 3,
-    Infotron = 4,
-    RamChip = 5,
-    Wall = 6,
-    Exit = 7,
-    FloppyOrange = 8,
-    PortRight = 9,
-    PortDown = 10,
-    PortLeft = 11,
-    PortUp = 12,
-    GravityPortRight = 13,
-    GravityPortDown = 14,
-    GravityPortLeft = 15,
-    GravityPortUp = 16,
-    SnikSnak = 17,
-    FloppyYellow = 18,
-    Terminal = 19,
-    FloppyRed = 20,
-    Port2WayVertical = 21,
-    Port2WayHorizontal = 22,
-    Port4Way = 23,
-    Electron = 24,
-    Bug = 25,
-    RamLeft = 26,
-    RamRight = 27,
-    Hardware1 = 28,
-    Hardware2 = 29,
-    Hardware3 = 30,
-    Hardware4 = 31,
-    Hardware5 = 32,
-    Hardware6 = 33,
-    Hardware7 = 34,
-    Hardware8 = 35,
-    Hardware9 = 36,
-    Hardware10 = 37,
-    RamTop = 38,
-    RamBottom = 39,
+pub struct Brush {
+    pub tile: Tile,
+    /// The offset in columns and rows of the second tile from the first one, and the tile.
+    pub second: Option<((isize, isize), Tile)>,
+}
+
+impl Brush {
+    pub fn new(tile: Tile) -> Self {
+        Self { tile, second: None }
+    }
+
+    /// The tiles placed when the brush is stamped at `index`. Empty if part of the brush
+    /// would end up outside the level.
+    pub fn tiles(&self, level: &Level, index: usize) -> Vec<(usize, Tile)> {
+        let mut tiles = vec![(index, self.tile)];
+        if let Some((offset, tile)) = self.second {
+            match level.offset_index(index, offset) {
+                Some(second_index) => tiles.push((second_index, tile)),
+                None => return vec![],
+            }
+        }
+        tiles
+    }
 }
 
 struct DrawingTool {
     tile1: Tile,
+    tile2: Option<Tile>,
     size: (u8, u8),
     image1: Rc<RetainedImage>,
 }
@@ -62,6 +48,7 @@ impl DrawingTool {
     pub fn new(tile: Tile, tool_panel: &ToolPanel) -> Self {
         Self {
             tile1: tile,
+            tile2: None,
             size: (1, 1),
             image1: tool_panel.images[tile].clone(),
         }
@@ -70,6 +57,7 @@ impl DrawingTool {
     fn new_n_by_n(tile: (Tile, Tile), size: (u8, u8), tool_panel: &ToolPanel) -> Self {
         Self {
             tile1: tile.0,
+            tile2: Some(tile.1),
             size,
             image1: tool_panel.images[tile.0].clone(),
         }
@@ -78,6 +66,13 @@ impl DrawingTool {
     pub fn tile(&self) -> Tile {
         self.tile1
     }
+
+    /// The brush of the tool. The second tile of a two-tile tool is to the right of the
+    /// first one if the tool is wider than it's tall, and below it otherwise.
+    pub fn brush(&self) -> Brush {
+        let offset = if self.size.0 > 1 { (1, 0) } else { (0, 1) };
+        Brush { tile: self.tile1, second: self.tile2.map(|tile| (offset, tile)) }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -91,19 +86,19 @@ pub struct ToolPanel
     width: f32,
     drawing_tools: Vec<DrawingTool>,
     selected_tool: usize,
-    images: Images,
+    images: Rc<Images>,
     operating_mode: OperatingMode,
 }
 
 impl ToolPanel {
 
-    pub fn new(heading: &str, width: f32) -> Self {
+    pub(crate) fn new(heading: &str, width: f32, images: Rc<Images>) -> Self {
         let mut res = Self {
             heading: heading.to_owned(),
             width,
             drawing_tools: vec![],
             selected_tool: 0,
-            images: Images::new(),
+            images,
             operating_mode: OperatingMode::Draw
         };
         res.drawing_tools = res.make_tools();
@@ -140,6 +135,10 @@ impl ToolPanel {
         self.operating_mode
     }
 
+    pub fn set_operating_mode(&mut self, operating_mode: OperatingMode) {
+        self.operating_mode = operating_mode;
+    }
+
     fn show_drawing_tools(& mut self, ui: &mut Ui, ctx: &Context) {
         ScrollArea::vertical().show(ui, |ui| {
             let layout = Layout::left_to_right(Align::Min).with_main_wrap(true);
@@ -172,6 +171,34 @@ impl ToolPanel {
         self.drawing_tools[self.selected_tool].tile()
     }
 
+    pub fn selected_brush(&self) -> Brush {
+        self.drawing_tools[self.selected_tool].brush()
+    }
+
+    /// Selects the next variant of the selected tool, e.g. the next disk colour.
+    pub fn select_next_variant(&mut self) {
+        self.select_tool_after(Tile::next_variant);
+    }
+
+    /// Selects the tool turned 90 degrees clockwise, e.g. the next port direction.
+    pub fn select_rotated(&mut self) {
+        self.select_tool_after(Tile::rotated);
+    }
+
+    /// Applies `next` to the tile of the selected tool until it gives one that there is a
+    /// tool for, skipping the tiles that can't be drawn directly, like gravity ports.
+    fn select_tool_after(&mut self, next: fn(Tile) -> Tile) {
+        let start = self.selected_tool_tile();
+        let mut tile = next(start);
+        while tile != start {
+            if let Some(index) = self.drawing_tools.iter().position(|tool| tool.tile() == tile) {
+                self.selected_tool = index;
+                return;
+            }
+            tile = next(tile);
+        }
+    }
+
     fn new_tool(&self, tile: Tile) -> DrawingTool {
         DrawingTool::new(tile, self)
     }
@@ -1,9 +1,21 @@
 use std::path::PathBuf;
 use egui::TopBottomPanel;
-use egui_file::FileDialog;
+use egui_file::{FileDialog, State};
+
+use crate::tile::Transform;
 
 enum DialogType {
-    Open, Save
+    Open, Save, Import, Export
+}
+
+/// Menu items that the application has to act on, e.g. by first asking what to do with
+/// unsaved changes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MenuCommand {
+    New, Open, Save, Exit,
+    /// Mirror or turn the selected tiles, or the whole level if nothing is selected.
+    Transform(Transform),
+    ZoomToFit, ActualSize,
 }
 
 /// The top panel is responsible for the menu bar, and the file dialogs for selecting the
@@ -11,13 +23,15 @@ enum DialogType {
 pub struct TopPanel {
     file_dialog: Option<FileDialog>,
     dialog_type: Option<DialogType>,
+    command: Option<MenuCommand>,
 }
 
 impl TopPanel {
     pub fn new() -> Self {
         Self {
             file_dialog: None,
-            dialog_type: None
+            dialog_type: None,
+            command: None,
         }
     }
 
@@ -27,12 +41,31 @@ impl TopPanel {
             .show(ctx, |ui| {
                 menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
-                    if ui.button("New").clicked() { todo!(); }
-                    if ui.button("Open...").clicked() { self.on_open(ui); }
-                    if ui.button("Save").clicked() { todo!(); }
+                    if ui.button("New").clicked() { self.on_command(MenuCommand::New, ui); }
+                    if ui.button("Open...").clicked() { self.on_command(MenuCommand::Open, ui); }
+                    if ui.button("Save").clicked() { self.on_command(MenuCommand::Save, ui); }
                     if ui.button("Save As...").clicked() { self.on_save_as(ui); }
                     ui.separator();
-                    if ui.button("Exit").clicked() { todo!(); }
+                    if ui.button("Import Level...").clicked() { self.on_import(ui); }
+                    if ui.button("Export Level...").clicked() { self.on_export(ui); }
+                    ui.separator();
+                    if ui.button("Exit").clicked() { self.on_command(MenuCommand::Exit, ui); }
+                });
+                ui.menu_button("Edit", |ui| {
+                    let hint = "Applies to the selection, or the whole level if nothing is selected";
+                    if ui.button("Flip Horizontally").on_hover_text(hint).clicked() {
+                        self.on_command(MenuCommand::Transform(Transform::FlipHorizontal), ui);
+                    }
+                    if ui.button("Flip Vertically").on_hover_text(hint).clicked() {
+                        self.on_command(MenuCommand::Transform(Transform::FlipVertical), ui);
+                    }
+                    if ui.button("Rotate Clockwise").on_hover_text(hint).clicked() {
+                        self.on_command(MenuCommand::Transform(Transform::RotateClockwise), ui);
+                    }
+                });
+                ui.menu_button("View", |ui| {
+                    if ui.button("Zoom to Fit").clicked() { self.on_command(MenuCommand::ZoomToFit, ui); }
+                    if ui.button("Actual Size (100%)").clicked() { self.on_command(MenuCommand::ActualSize, ui); }
                 });
             });
         });
@@ -40,9 +73,22 @@ impl TopPanel {
             if dlg.visible() {
                 dlg.show(ctx);
             }
+            if matches!(dlg.state(), State::Closed | State::Cancelled) {
+                self.close_dialog();
+            }
         }
     }
 
+    /// Returns the menu command selected since the previous call, if any.
+    pub fn take_command(&mut self) -> Option<MenuCommand> {
+        self.command.take()
+    }
+
+    /// Indicates whether a file dialog is shown.
+    pub fn dialog_open(&self) -> bool {
+        self.file_dialog.is_some()
+    }
+
     /// Indicates whether "Open..." menu item was selected.
     pub fn open_selected(&self) -> bool {
         matches!(self.dialog_type, Some(DialogType::Open))
@@ -53,6 +99,16 @@ impl TopPanel {
         matches!(self.dialog_type, Some(DialogType::Save))
     }
 
+    /// Indicates whether "Import level..." menu item was selected.
+    pub fn import_selected(&self) -> bool {
+        matches!(self.dialog_type, Some(DialogType::Import))
+    }
+
+    /// Indicates whether "Export level..." menu item was selected.
+    pub fn export_selected(&self) -> bool {
+        matches!(self.dialog_type, Some(DialogType::Export))
+    }
+
     /// Indicates whether a path was selected using the file dialog.
     pub fn path_selected(&self) -> bool {
         match &self.file_dialog {
@@ -76,19 +132,45 @@ impl TopPanel {
         self.dialog_type = None;
     }
 
-    fn on_save_as(&mut self, ui: &mut egui::Ui) {
+    /// Shows the file dialog for selecting the file to save as.
+    pub fn show_save_as_dialog(&mut self) {
         let mut dlg = FileDialog::save_file(None).show_rename(false);
         dlg.open();
         self.file_dialog = Some(dlg);
         self.dialog_type = Some(DialogType::Save);
-        ui.close_menu();
     }
 
-    fn on_open(&mut self, ui: &mut egui::Ui) {
+    /// Shows the file dialog for selecting the file to open.
+    pub fn show_open_dialog(&mut self) {
         let mut dlg = FileDialog::open_file(None).show_new_folder(false).show_rename(false);//.filter(String::from("*.dat"));
         dlg.open();
         self.file_dialog = Some(dlg);
         self.dialog_type = Some(DialogType::Open);
+    }
+
+    fn on_command(&mut self, command: MenuCommand, ui: &mut egui::Ui) {
+        self.command = Some(command);
+        ui.close_menu();
+    }
+
+    fn on_save_as(&mut self, ui: &mut egui::Ui) {
+        self.show_save_as_dialog();
+        ui.close_menu();
+    }
+
+    fn on_import(&mut self, ui: &mut egui::Ui) {
+        let mut dlg = FileDialog::open_file(None).show_new_folder(false).show_rename(false);
+        dlg.open();
+        self.file_dialog = Some(dlg);
+        self.dialog_type = Some(DialogType::Import);
+        ui.close_menu();
+    }
+
+    fn on_export(&mut self, ui: &mut egui::Ui) {
+        let mut dlg = FileDialog::save_file(None).show_rename(false);
+        dlg.open();
+        self.file_dialog = Some(dlg);
+        self.dialog_type = Some(DialogType::Export);
         ui.close_menu();
     }
 }
@@ -0,0 +1,288 @@
+// Checks for mistakes that make a level unplayable or behave differently than intended.
+
+use std::fmt;
+
+use crate::level::{Direction, Level, MAX_SPECIAL_PORTS};
+use crate::simulation::is_rounded;
+use crate::tile::Tile;
+
+/// A problem found in a level.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problem {
+    NoMurphy,
+    /// One of the Murphys of a level that has more than one.
+    ExtraMurphy { count: usize },
+    NoExit,
+    TooFewInfotrons { needed: usize, available: usize },
+    /// The first gravity port that can't have a special port table entry.
+    TooManyGravityPorts { count: usize },
+    /// The first border tile that isn't hardware.
+    OpenBorder { count: usize },
+    /// Half of a two-tile RAM chip without the other half next to it.
+    UnpairedRamChip,
+    /// A zonk, infotron or orange disk with nothing below it.
+    FallsAtStart,
+    /// A zonk, infotron or orange disk with nothing between it and Murphy below it.
+    FallsOntoMurphy,
+    /// A zonk or infotron on something rounded, with room to roll off it.
+    RollsAtStart,
+    /// A snik snak or electron right next to Murphy.
+    EnemyNextToMurphy,
+}
+
+impl Problem {
+    /// Indicates whether the problem is about something that happens as soon as the level
+    /// starts, which may well be intended.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, Problem::FallsAtStart | Problem::FallsOntoMurphy | Problem::RollsAtStart | Problem::EnemyNextToMurphy)
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::NoMurphy => write!(f, "No Murphy"),
+            Problem::ExtraMurphy { count } => write!(f, "{} Murphys, there should be one", count),
+            Problem::NoExit => write!(f, "No Exit"),
+            Problem::TooFewInfotrons { needed, available } =>
+                write!(f, "{} infotrons needed, only {} in the level", needed, available),
+            Problem::TooManyGravityPorts { count } =>
+                write!(f, "{} gravity ports, at most {} can be special", count, MAX_SPECIAL_PORTS),
+            Problem::OpenBorder { count } => write!(f, "{} border tiles aren't hardware", count),
+            Problem::UnpairedRamChip => write!(f, "Half of a RAM chip"),
+            Problem::FallsAtStart => write!(f, "Falls as soon as the level starts"),
+            Problem::FallsOntoMurphy => write!(f, "Falls onto Murphy as soon as the level starts"),
+            Problem::RollsAtStart => write!(f, "Rolls off as soon as the level starts"),
+            Problem::EnemyNextToMurphy => write!(f, "Enemy next to Murphy at the start"),
+        }
+    }
+}
+
+/// A problem and the tile it concerns, if any.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub problem: Problem,
+    pub tile_index: Option<usize>,
+}
+
+impl Diagnostic {
+    fn new(problem: Problem, tile_index: Option<usize>) -> Self {
+        Self { problem, tile_index }
+    }
+}
+
+/// Checks the level for problems, returning them in the order of the checks. The things
+/// that move as soon as the level starts come last, as warnings.
+pub fn validate(level: &Level) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let positions = |tile: Tile| -> Vec<usize> {
+        level.play_area.iter().enumerate().filter(|(_, &t)| t == tile).map(|(i, _)| i).collect()
+    };
+
+    let murphys = positions(Tile::Murphy);
+    match murphys.len() {
+        0 => diagnostics.push(Diagnostic::new(Problem::NoMurphy, None)),
+        1 => {}
+        count => diagnostics.extend(murphys.iter()
+            .map(|&i| Diagnostic::new(Problem::ExtraMurphy { count }, Some(i)))),
+    }
+
+    if positions(Tile::Exit).is_empty() {
+        diagnostics.push(Diagnostic::new(Problem::NoExit, None));
+    }
+
+    let needed = level.info.infotrons_needed() as usize;
+    let available = level.infotron_count();
+    if needed > available {
+        diagnostics.push(Diagnostic::new(Problem::TooFewInfotrons { needed, available }, None));
+    }
+
+    let gravity_ports: Vec<usize> = (0..level.play_area.len())
+        .filter(|&i| level.play_area[i].is_gravity_port())
+        .collect();
+    if gravity_ports.len() > MAX_SPECIAL_PORTS {
+        let problem = Problem::TooManyGravityPorts { count: gravity_ports.len() };
+        diagnostics.push(Diagnostic::new(problem, Some(gravity_ports[MAX_SPECIAL_PORTS])));
+    }
+
+    let (width, height) = (level.width(), level.height());
+    let open_border: Vec<usize> = (0..level.play_area.len())
+        .filter(|&i| {
+            let (col, row) = level.col_row(i);
+            (col == 0 || row == 0 || col == width - 1 || row == height - 1) && !level.play_area[i].is_hardware()
+        })
+        .collect();
+    if let Some(&first) = open_border.first() {
+        diagnostics.push(Diagnostic::new(Problem::OpenBorder { count: open_border.len() }, Some(first)));
+    }
+
+    for index in 0..level.play_area.len() {
+        if let Some((offset, tile)) = level.play_area[index].ram_chip_partner() {
+            if level.offset_index(index, offset).is_none_or(|partner| level.play_area[partner] != tile) {
+                diagnostics.push(Diagnostic::new(Problem::UnpairedRamChip, Some(index)));
+            }
+        }
+    }
+    diagnostics.extend(instabilities(level));
+    diagnostics
+}
+
+/// Finds the objects that fall or roll off as soon as the level starts, and the enemies
+/// next to Murphy, by the same rules as the simulation.
+pub fn instabilities(level: &Level) -> Vec<Diagnostic> {
+    let is_empty = |index: Option<usize>| index.is_some_and(|i| level.play_area[i] == Tile::Empty);
+    let mut diagnostics = vec![];
+    for index in 0..level.play_area.len() {
+        let tile = level.play_area[index];
+        let falls = match tile {
+            Tile::Zonk => !level.info.freeze_zonks(),
+            Tile::Infotron | Tile::FloppyOrange => true,
+            _ => false,
+        };
+        let below = level.neighbour(index, Direction::Down);
+        if falls && is_empty(below) {
+            let mut landing = below;
+            while is_empty(landing) {
+                landing = landing.and_then(|i| level.neighbour(i, Direction::Down));
+            }
+            let onto_murphy = landing.is_some_and(|i| level.play_area[i] == Tile::Murphy);
+            let problem = if onto_murphy { Problem::FallsOntoMurphy } else { Problem::FallsAtStart };
+            diagnostics.push(Diagnostic::new(problem, Some(index)));
+        }
+        else if falls && tile != Tile::FloppyOrange && below.is_some_and(|i| is_rounded(level.play_area[i])) {
+            let rolls = [Direction::Left, Direction::Right].into_iter().any(|side| {
+                let beside = level.neighbour(index, side);
+                is_empty(beside) && is_empty(beside.and_then(|i| level.neighbour(i, Direction::Down)))
+            });
+            if rolls {
+                diagnostics.push(Diagnostic::new(Problem::RollsAtStart, Some(index)));
+            }
+        }
+        else if matches!(tile, Tile::SnikSnak | Tile::Electron) {
+            let next_to_murphy = Direction::ALL.into_iter()
+                .any(|direction| level.neighbour(index, direction).is_some_and(|i| level.play_area[i] == Tile::Murphy));
+            if next_to_murphy {
+                diagnostics.push(Diagnostic::new(Problem::EnemyNextToMurphy, Some(index)));
+            }
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_file::test_level;
+
+    fn problems(fields: &str, rows: &[&str]) -> Vec<(Problem, Option<(usize, usize)>)> {
+        let level = test_level(fields, rows);
+        validate(&level).into_iter()
+            .map(|diagnostic| (diagnostic.problem, diagnostic.tile_index.map(|i| level.col_row(i))))
+            .collect()
+    }
+
+    #[test]
+    fn valid_level() {
+        assert_eq!(problems("", &[
+            "######",
+            "#M:IE#",
+            "######",
+        ]), []);
+    }
+
+    #[test]
+    fn murphys() {
+        assert_eq!(problems("", &[
+            "#####",
+            "#..E#",
+            "#####",
+        ]), [(Problem::NoMurphy, None)]);
+        assert_eq!(problems("", &[
+            "#####",
+            "#MME#",
+            "#####",
+        ]), [(Problem::ExtraMurphy { count: 2 }, Some((1, 1))), (Problem::ExtraMurphy { count: 2 }, Some((2, 1)))]);
+    }
+
+    #[test]
+    fn exit_and_infotrons() {
+        assert_eq!(problems("infotrons_needed = 2", &[
+            "#####",
+            "#M.I#",
+            "#####",
+        ]), [(Problem::NoExit, None), (Problem::TooFewInfotrons { needed: 2, available: 1 }, None)]);
+    }
+
+    #[test]
+    fn open_border() {
+        assert_eq!(problems("", &[
+            "##.##",
+            "#M.E.",
+            "#####",
+        ]), [(Problem::OpenBorder { count: 2 }, Some((2, 0)))]);
+    }
+
+    #[test]
+    fn unpaired_ram_chips() {
+        assert_eq!(problems("", &[
+            "########",
+            "#M().(E#",
+            "#n.u...#",
+            "########",
+        ]), [
+            (Problem::UnpairedRamChip, Some((5, 1))),
+            (Problem::UnpairedRamChip, Some((1, 2))),
+            (Problem::UnpairedRamChip, Some((3, 2))),
+        ]);
+    }
+
+    fn instabilities_of(fields: &str, rows: &[&str]) -> Vec<(Problem, (usize, usize))> {
+        let level = test_level(fields, rows);
+        instabilities(&level).into_iter()
+            .map(|diagnostic| (diagnostic.problem, level.col_row(diagnostic.tile_index.unwrap())))
+            .collect()
+    }
+
+    #[test]
+    fn falls_onto_murphy_or_not() {
+        assert_eq!(instabilities_of("", &[
+            "O.I.",
+            "....",
+            "M.:.",
+        ]), [(Problem::FallsOntoMurphy, (0, 0)), (Problem::FallsAtStart, (2, 0))]);
+    }
+
+    #[test]
+    fn frozen_zonks_dont_fall() {
+        assert_eq!(instabilities_of("freeze_zonks = 2", &[
+            "O",
+            ".",
+        ]), []);
+    }
+
+    #[test]
+    fn rolls_off_rounded_tiles() {
+        assert_eq!(instabilities_of("", &[
+            ".O.O#",
+            ".O#R#",
+            "#####",
+        ]), [(Problem::RollsAtStart, (1, 0))]);
+    }
+
+    #[test]
+    fn enemy_next_to_murphy() {
+        assert_eq!(instabilities_of("", &[
+            "SM.e",
+            "####",
+        ]), [(Problem::EnemyNextToMurphy, (0, 0))]);
+    }
+
+    #[test]
+    fn too_many_gravity_ports() {
+        assert_eq!(problems("", &[
+            "###############",
+            "#M}}}}}}}}}}}E#",
+            "###############",
+        ]), [(Problem::TooManyGravityPorts { count: 11 }, Some((12, 1)))]);
+    }
+}